@@ -1,23 +1,104 @@
+use std::any::Any;
 use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, HashSet};
-use std::io::{Error, ErrorKind};
+use std::fmt;
+use std::fs;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::{Error, ErrorKind, IsTerminal, Write};
+use std::num::IntErrorKind;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
 use std::str::FromStr;
+use std::time::Duration;
+
+pub mod validators;
 
 pub trait ValueHandler {
     fn parse_value(&self, value: &str) -> bool;
     fn requires_value(&self) -> bool;
     fn set_value(&self);
     fn value_type(&self) -> String;
+    /// Canonical string form of the current value, e.g. for logging the effective configuration.
+    fn value_string(&self) -> String;
+    /// Number of values a value-taking switch consumes, e.g. 3 for "--point 1 2 3".
+    fn value_count(&self) -> usize {
+        1
+    }
+    /// Called instead of parse_value when value_count is greater than 1.
+    fn parse_values(&self, values: &[&str]) -> bool {
+        values.len() == 1 && self.parse_value(values[0])
+    }
+    /// Explains why parse_value rejected the value, called only after a failed parse.
+    fn error_kind(&self, _value: &str) -> ParseErrorKind {
+        ParseErrorKind::InvalidValue
+    }
+    /// Message for a value rejected by parse_value, e.g. naming the bad element of a list.
+    /// None uses the Messages template for error_kind.
+    fn error_message(&self, _value: &str) -> Option<String> {
+        None
+    }
+    /// Called after all arguments are consumed; an error fails Arguments::build.
+    /// The message is not translated through Messages.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+    /// Called at the start of each Arguments::build, e.g. to forget a conflict found by the previous call.
+    /// Parsed values are kept.
+    fn reset(&self) {
+    }
+}
+
+fn parse_env_default(parameter: &dyn ValueHandler, name: &str) {
+    if let Ok(value) = std::env::var(name) {
+        parameter.parse_value(&value);
+    }
 }
 
 pub struct IntParameter {
     value: Cell<isize>,
-    validator: fn(isize) -> bool
+    validator: Box<dyn Fn(isize) -> bool>,
 }
 
 impl IntParameter {
-    pub fn new(value: isize, validator: fn(isize) -> bool) -> IntParameter {
-        IntParameter { validator, value: Cell::new(value) }
+    /// The validator can be a closure or one of the validators module functions.
+    /// Panics if the default value is rejected by the validator.
+    pub fn new(value: isize, validator: impl Fn(isize) -> bool + 'static) -> IntParameter {
+        match IntParameter::try_new(value, validator) {
+            Ok(p) => p,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    /// Same as new, but returns an error if the default value is rejected by the validator.
+    pub fn try_new(value: isize, validator: impl Fn(isize) -> bool + 'static) -> Result<IntParameter, Error> {
+        if !validator(value) {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("int default value {} is rejected by the validator", value)));
+        }
+        Ok(IntParameter { validator: Box::new(validator), value: Cell::new(value) })
+    }
+
+    /// Also rejects values below min, e.g. IntParameter::new(6379, |_| true).with_min(1).with_max(65535).
+    /// Panics if the default value is below min.
+    pub fn with_min(self, min: isize) -> IntParameter {
+        let validator = self.validator;
+        IntParameter::new(self.value.get(), move |v| v >= min && validator(v))
+    }
+
+    /// Also rejects values above max. Panics if the default value is above max.
+    pub fn with_max(self, max: isize) -> IntParameter {
+        let validator = self.validator;
+        IntParameter::new(self.value.get(), move |v| v <= max && validator(v))
+    }
+
+    /// Takes the default from the environment variable, e.g. from_env("PORT", 6379, validators::positive).
+    /// When the variable is not set or its value is invalid, the given default is used.
+    pub fn from_env(name: &str, value: isize, validator: impl Fn(isize) -> bool + 'static) -> IntParameter {
+        let parameter = IntParameter::new(value, validator);
+        parse_env_default(&parameter, name);
+        parameter
     }
 
     pub fn get_value(&self) -> isize {
@@ -37,14 +118,91 @@ impl ValueHandler for IntParameter {
     }
 
     fn requires_value(&self) -> bool {
-        return true;
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " int".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.get().to_string()
+    }
+
+    fn error_kind(&self, value: &str) -> ParseErrorKind {
+        int_error_kind(value)
+    }
+}
+
+/// Error kind for a value rejected by an isize handler: out of range, not a number or rejected by a validator.
+fn int_error_kind(value: &str) -> ParseErrorKind {
+    match isize::from_str(value) {
+        Err(e) if matches!(e.kind(), IntErrorKind::PosOverflow | IntErrorKind::NegOverflow) =>
+            ParseErrorKind::ValueOutOfRange,
+        Err(_) => ParseErrorKind::NotANumber,
+        Ok(_) => ParseErrorKind::InvalidValue
+    }
+}
+
+/// Integer that must be a power of two, e.g. a buffer size or an alignment.
+pub struct PowerOfTwoParameter {
+    value: Cell<isize>,
+}
+
+impl PowerOfTwoParameter {
+    /// Panics if the default value is not a power of two.
+    pub fn new(value: isize) -> PowerOfTwoParameter {
+        match PowerOfTwoParameter::try_new(value) {
+            Ok(p) => p,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    /// Same as new, but returns an error if the default value is not a power of two.
+    pub fn try_new(value: isize) -> Result<PowerOfTwoParameter, Error> {
+        if !validators::power_of_two(value) {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("int default value {} is not a power of two", value)));
+        }
+        Ok(PowerOfTwoParameter { value: Cell::new(value) })
+    }
+
+    pub fn get_value(&self) -> isize {
+        self.value.get()
+    }
+}
+
+impl ValueHandler for PowerOfTwoParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        if let Ok(v) = isize::from_str(value) {
+            if validators::power_of_two(v) {
+                self.value.set(v);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn requires_value(&self) -> bool {
+        true
     }
 
     fn set_value(&self) {
     }
 
     fn value_type(&self) -> String {
-        return " int".to_string()
+        " int".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.get().to_string()
+    }
+
+    fn error_kind(&self, value: &str) -> ParseErrorKind {
+        int_error_kind(value)
     }
 }
 
@@ -57,6 +215,13 @@ impl StringParameter {
         StringParameter { value: RefCell::new(value.to_string()) }
     }
 
+    /// Takes the default from the environment variable, the given default is used when it is not set.
+    pub fn from_env(name: &str, value: &str) -> StringParameter {
+        let parameter = StringParameter::new(value);
+        parse_env_default(&parameter, name);
+        parameter
+    }
+
     pub fn get_value(&self) -> String {
         self.value.borrow().clone()
     }
@@ -69,25 +234,29 @@ impl ValueHandler for StringParameter {
     }
 
     fn requires_value(&self) -> bool {
-        return true;
+        true
     }
 
     fn set_value(&self) {
     }
 
     fn value_type(&self) -> String {
-        return " string".to_string()
+        " string".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().clone()
     }
 }
 
-pub struct EnumParameter {
-    values: HashSet<String>,
+/// Same as StringParameter, but rejects empty and whitespace-only values.
+pub struct NonEmptyStringParameter {
     value: RefCell<String>,
 }
 
-impl EnumParameter {
-    pub fn new(values: Vec<String>, value: &str) -> EnumParameter {
-        EnumParameter { values: values.into_iter().collect(), value: RefCell::new(value.to_string()) }
+impl NonEmptyStringParameter {
+    pub fn new(value: &str) -> NonEmptyStringParameter {
+        NonEmptyStringParameter { value: RefCell::new(value.to_string()) }
     }
 
     pub fn get_value(&self) -> String {
@@ -95,317 +264,5365 @@ impl EnumParameter {
     }
 }
 
-impl ValueHandler for EnumParameter {
+impl ValueHandler for NonEmptyStringParameter {
     fn parse_value(&self, value: &str) -> bool {
-        if self.values.contains(value) {
-            *self.value.borrow_mut() = value.to_string();
-            true
-        } else {
-            false
+        if value.trim().is_empty() {
+            return false;
         }
+        *self.value.borrow_mut() = value.to_string();
+        true
     }
 
     fn requires_value(&self) -> bool {
-        return true;
+        true
     }
 
     fn set_value(&self) {
     }
 
     fn value_type(&self) -> String {
-        let mut t = " ".to_string();
-        let mut first = true;
-        for v in &self.values {
-            if first {
-                first = false;
-            } else {
-                t.push('|');
-            }
-            t.push_str(v.as_str());
-        }
-        return t
+        " string".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().clone()
     }
 }
 
-pub struct BoolParameter {
-    value: Cell<bool>,
+/// String with a maximum length, e.g. a 256 byte "--comment". The length is counted in bytes,
+/// or in characters after count_chars.
+pub struct BoundedStringParameter {
+    max_len: usize,
+    count_chars: bool,
+    value: RefCell<String>,
 }
 
-impl BoolParameter {
-    pub fn new() -> BoolParameter {
-        BoolParameter { value: Cell::new(false) }
+impl BoundedStringParameter {
+    pub fn new(max_len: usize, value: &str) -> BoundedStringParameter {
+        BoundedStringParameter { max_len, count_chars: false, value: RefCell::new(value.to_string()) }
     }
 
-    pub fn get_value(&self) -> bool {
-        self.value.get()
+    pub fn count_chars(mut self) -> BoundedStringParameter {
+        self.count_chars = true;
+        self
+    }
+
+    pub fn get_value(&self) -> String {
+        self.value.borrow().clone()
     }
 }
 
-impl ValueHandler for BoolParameter {
-    fn parse_value(&self, _value: &str) -> bool {
-        // should not be called
-        false
+impl ValueHandler for BoundedStringParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        let len = if self.count_chars { value.chars().count() } else { value.len() };
+        if len > self.max_len {
+            return false;
+        }
+        *self.value.borrow_mut() = value.to_string();
+        true
     }
 
     fn requires_value(&self) -> bool {
-        return false;
+        true
     }
 
     fn set_value(&self) {
-        self.value.set(true);
     }
 
     fn value_type(&self) -> String {
-        return "".to_string()
+        " string".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().clone()
     }
 }
 
-pub struct SizeParameter {
-    value: Cell<isize>,
-    validator: fn(isize) -> bool
+/// E-mail address. Only the shape is checked (a single '@', non-empty local part, a domain with a dot),
+/// this is not a full RFC 5322 validation.
+pub struct EmailParameter {
+    value: RefCell<String>,
 }
 
-impl SizeParameter {
-    pub fn new(value: isize, validator: fn(isize) -> bool) -> SizeParameter {
-        SizeParameter { value: Cell::new(value), validator }
+impl EmailParameter {
+    pub fn new(value: &str) -> EmailParameter {
+        EmailParameter { value: RefCell::new(value.to_string()) }
     }
 
-    pub fn get_value(&self) -> isize {
-        self.value.get()
+    pub fn get_value(&self) -> String {
+        self.value.borrow().clone()
     }
 }
 
-impl ValueHandler for SizeParameter {
+fn is_email_like(value: &str) -> bool {
+    if value.contains(char::is_whitespace) {
+        return false;
+    }
+    match value.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && !domain.contains('@') =>
+            domain.contains('.') && domain.split('.').all(|part| !part.is_empty()),
+        _ => false
+    }
+}
+
+impl ValueHandler for EmailParameter {
     fn parse_value(&self, value: &str) -> bool {
-        if value.len() == 0 {
-            return false
-        }
-        let multiplier = match value.chars().last().unwrap() {
-            'M' => 1024 * 1024,
-            'K' => 1024,
-            'G' => 1024 * 1024 * 1024,
-            _ => 1
-        };
-        if let Ok(size) = if multiplier == 1 {
-            isize::from_str(value)
+        if is_email_like(value) {
+            *self.value.borrow_mut() = value.to_string();
+            true
         } else {
-            let mut chars = value.chars();
-            chars.next_back();
-            isize::from_str(chars.as_str())
-        } {
-            let msize = size * multiplier;
-            if (self.validator)(msize) {
-                self.value.set(msize);
-                return true;
-            }
+            false
         }
-        false
     }
 
     fn requires_value(&self) -> bool {
-        return true;
+        true
     }
 
     fn set_value(&self) {
     }
 
     fn value_type(&self) -> String {
-        return " size".to_string()
+        " email".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().clone()
     }
 }
 
-#[derive(Clone)]
-pub struct Switch<'a> {
-    name: String,
-    switch: Option<char>,
-    ext_switch: Option<String>,
-    handler: &'a dyn ValueHandler,
+/// URL like "https://host/path" with one of the allowed schemes (compared case-insensitively, any scheme
+/// when the list is empty). Only the scheme and a non-empty host are checked, the rest is taken as is.
+pub struct UrlParameter {
+    schemes: Vec<String>,
+    value: RefCell<String>,
 }
 
-impl<'a> Switch<'a> {
-    pub fn new(name: &str, switch: Option<char>, ext_switch: Option<&str>, handler: &'a dyn ValueHandler) -> Switch<'a> {
-        Switch {
-            name: name.to_string(),
-            switch,
-            ext_switch: ext_switch.map(|s|s.to_string()),
-            handler,
+impl UrlParameter {
+    pub fn new(schemes: Vec<String>, value: &str) -> UrlParameter {
+        UrlParameter {
+            schemes: schemes.iter().map(|s| s.to_lowercase()).collect(),
+            value: RefCell::new(value.to_string()),
         }
     }
 
-    fn to_string(&self) -> String {
-        let mut result = "".to_string();
-        if let Some(sw) = self.switch {
-            result.push_str(format!(" -{}", sw).as_str());
-            if let Some(sw) = &self.ext_switch {
-                result.push_str(format!(" (or --{})", sw).as_str());
-            }
-        } else if let Some(sw) = &self.ext_switch {
-            result.push_str(format!(" --{}", sw).as_str());
-        }
-        result.push_str(self.handler.value_type().as_str());
-        result.push_str(" - ");
-        result.push_str(self.name.as_str());
-        result
+    pub fn get_value(&self) -> String {
+        self.value.borrow().clone()
+    }
+}
+
+fn url_scheme(value: &str) -> Option<String> {
+    let (scheme, rest) = value.split_once("://")?;
+    let mut chars = scheme.chars();
+    if !chars.next()?.is_ascii_alphabetic() || !chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) {
+        return None;
     }
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    if host.is_empty() || value.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(scheme.to_lowercase())
+}
 
+impl ValueHandler for UrlParameter {
     fn parse_value(&self, value: &str) -> bool {
-        self.handler.parse_value(value)
+        match url_scheme(value) {
+            Some(scheme) if self.schemes.is_empty() || self.schemes.contains(&scheme) => {
+                *self.value.borrow_mut() = value.to_string();
+                true
+            }
+            _ => false
+        }
     }
 
     fn requires_value(&self) -> bool {
-        self.handler.requires_value()
+        true
     }
 
     fn set_value(&self) {
-        self.handler.set_value()
+    }
+
+    fn value_type(&self) -> String {
+        " url".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().clone()
     }
 }
 
-pub struct Arguments<'a> {
-    program_name: String,
-    switch_map: HashMap<char, Switch<'a>>,
-    ext_switch_map: HashMap<String, Switch<'a>>,
-    other_arguments: Vec<String>,
-    other_argument_names: Option<Vec<String>>,
+/// String parameter that expands $VAR and ${VAR} references from the process environment.
+/// Unknown variables expand to an empty string, like in a shell. A '$' that is not followed by
+/// a variable name (or an unterminated "${") is kept as is.
+pub struct ExpandingStringParameter {
+    value: RefCell<String>,
 }
 
-impl<'a> Arguments<'a> {
-    pub fn new(program_name: &str, switches: &[Switch<'a>], other_argument_names: Option<Vec<String>>) -> Arguments<'a> {
-        let mut switch_map = HashMap::new();
-        let mut ext_switch_map = HashMap::new();
-        for switch in switches {
-            if let Some(sw) = switch.switch {
-                switch_map.insert(sw, switch.clone());
-            }
-            if let Some(sw) = &switch.ext_switch {
-                ext_switch_map.insert(sw.clone(), switch.clone());
-            }
-        }
-        Arguments {
-            program_name: program_name.to_string(),
-            switch_map,
-            ext_switch_map,
-            other_arguments: Vec::new(),
-            other_argument_names
-        }
+impl ExpandingStringParameter {
+    pub fn new(value: &str) -> ExpandingStringParameter {
+        ExpandingStringParameter { value: RefCell::new(value.to_string()) }
     }
 
-    pub fn usage(&self) {
-        let mut usage = "Usage: ".to_string();
-        usage.push_str(&self.program_name);
-        if let Some(other_argument_names) = self.other_argument_names.as_ref() {
-            for name in other_argument_names {
-                usage.push_str((" ".to_string() + name.as_str()).as_str())
-            }
-        }
-        usage.push('\n');
-        for (_name, sw) in &self.switch_map {
-            usage.push_str(sw.to_string().as_str());
-            usage.push('\n');
-        }
-        for (_name, sw) in &self.ext_switch_map {
-            usage.push_str(sw.to_string().as_str());
-            usage.push('\n');
-        }
-        println!("{}", usage);
+    pub fn get_value(&self) -> String {
+        self.value.borrow().clone()
     }
+}
 
-    pub fn build(&mut self, args: Vec<String>) -> Result<(), Error> {
-        let mut current_parameter: Option<&Switch> = None;
-        for arg in args {
-            if let Some(p) = current_parameter {
-                if !p.parse_value(arg.as_str()) {
-                   return Err(Error::new(ErrorKind::InvalidInput,
-                                            format!("invalid {} value", p.name)))?;
-                }
-                current_parameter = None;
-            } else {
-                if arg.starts_with('-') {
-                    if arg.starts_with("--") {
-                        if arg.len() == 2 {
-                            return Err(Error::new(ErrorKind::InvalidInput, "invalid ext_switch"));
-                        }
-                        if let Some(p) = self.ext_switch_map.get(&arg.chars().skip(2).collect::<String>()) {
-                            if p.requires_value() {
-                                current_parameter = Some(p);
-                            } else {
-                                p.set_value();
-                            }
-                        } else {
-                            return Err(Error::new(ErrorKind::InvalidInput, "unknown ext switch"));
-                        }
-                    } else {
-                        if arg.len() != 2 {
-                            return Err(Error::new(ErrorKind::InvalidInput, "invalid switch"));
-                        }
-                        if let Some(p) = self.switch_map.get(&arg.chars().skip(1).next().unwrap()) {
-                            if p.requires_value() {
-                                current_parameter = Some(p);
-                            } else {
-                                p.set_value();
-                            }
-                        } else {
-                            return Err(Error::new(ErrorKind::InvalidInput, "unknown switch"));
-                        }
-                    }
-                } else {
-                    self.other_arguments.push(arg.clone());
-                }
-            }
-        }
-        if current_parameter.is_some() {
-            return Err(Error::new(ErrorKind::InvalidInput, "switch value expected"));
-        }
-        if let Some(other_argument_names) = self.other_argument_names.as_ref() {
-            if other_argument_names.len() != self.other_arguments.len() {
-                return Err(Error::new(ErrorKind::InvalidInput, "incorrect number of arguments"));
+fn expand_env_vars(value: &str) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        let (name, tail) = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", braced)
             }
+        } else {
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        if name.is_empty() {
+            result.push('$');
+        } else {
+            result.push_str(std::env::var(name).unwrap_or_default().as_str());
+            rest = tail;
         }
-        Ok(())
     }
+    result.push_str(rest);
+    result
+}
 
-    pub fn get_other_arguments(&self) -> &Vec<String> {
-        &self.other_arguments
+impl ValueHandler for ExpandingStringParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        *self.value.borrow_mut() = expand_env_vars(value);
+        true
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::{Arguments, BoolParameter, EnumParameter, IntParameter, SizeParameter, StringParameter, Switch};
+    fn requires_value(&self) -> bool {
+        true
+    }
 
-    #[test]
-    fn test_arguments_parser() {
-        let port_parameter = IntParameter::new(6379, |v|v>0);
-        let max_memory_parameter = SizeParameter::new(1024 * 1024 * 1024, |v|v>0);//1G
-        let threads_parameter = IntParameter::new(4, |v|v>0);
-        let verbose_parameter = BoolParameter::new();
-        let string_parameter = StringParameter::new("init");
-        let enum_parameter = EnumParameter::new(vec!["value".to_string()], "init");
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " string".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().clone()
+    }
+}
+
+pub struct EnumParameter {
+    values: HashSet<String>,
+    value: RefCell<String>,
+    allow_abbreviated_values: bool,
+}
+
+impl EnumParameter {
+    /// Panics if the default value is neither one of the allowed values nor an empty string.
+    pub fn new(values: Vec<String>, value: &str) -> EnumParameter {
+        match EnumParameter::try_new(values, value) {
+            Ok(p) => p,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    /// Same as new, but returns an error if the default value is neither one of the allowed values
+    /// nor an empty string (meaning "no value").
+    pub fn try_new(values: Vec<String>, value: &str) -> Result<EnumParameter, Error> {
+        let values: HashSet<String> = values.into_iter().collect();
+        if !value.is_empty() && !values.contains(value) {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("enum default value {} is not one of the allowed values", value)));
+        }
+        Ok(EnumParameter { values, value: RefCell::new(value.to_string()), allow_abbreviated_values: false })
+    }
+
+    /// Accepts an unambiguous prefix of an allowed value (e.g. "i" for "info"). Exact matches always win.
+    pub fn allow_abbreviated_values(mut self) -> EnumParameter {
+        self.allow_abbreviated_values = true;
+        self
+    }
+
+    pub fn get_value(&self) -> String {
+        self.value.borrow().clone()
+    }
+}
+
+impl ValueHandler for EnumParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        if self.values.contains(value) {
+            *self.value.borrow_mut() = value.to_string();
+            return true;
+        }
+        if self.allow_abbreviated_values && !value.is_empty() {
+            let mut candidates = self.values.iter().filter(|v| v.starts_with(value));
+            if let (Some(candidate), None) = (candidates.next(), candidates.next()) {
+                *self.value.borrow_mut() = candidate.clone();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        let mut t = " ".to_string();
+        let mut first = true;
+        for v in &self.values {
+            if first {
+                first = false;
+            } else {
+                t.push('|');
+            }
+            t.push_str(v.as_str());
+        }
+        t
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().clone()
+    }
+}
+
+/// Comma-separated list of allowed values, e.g. "--features a,b,c".
+/// An element that is not allowed fails Arguments::build with an error naming it.
+pub struct MultiEnumParameter {
+    values: HashSet<String>,
+    value: RefCell<Vec<String>>,
+}
+
+impl MultiEnumParameter {
+    pub fn new(values: Vec<String>, value: Vec<String>) -> MultiEnumParameter {
+        MultiEnumParameter { values: values.into_iter().collect(), value: RefCell::new(value) }
+    }
+
+    pub fn get_value(&self) -> Vec<String> {
+        self.value.borrow().clone()
+    }
+
+    fn invalid_element<'v>(&self, value: &'v str) -> Option<&'v str> {
+        value.split(',').map(|v| v.trim()).find(|v| !self.values.contains(*v))
+    }
+}
+
+impl ValueHandler for MultiEnumParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        if self.invalid_element(value).is_some() {
+            return false;
+        }
+        *self.value.borrow_mut() = value.split(',').map(|v| v.trim().to_string()).collect();
+        true
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        let mut values: Vec<&str> = self.values.iter().map(|v| v.as_str()).collect();
+        values.sort();
+        format!(" {}[,...]", values.join("|"))
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().join(",")
+    }
+
+    fn error_message(&self, value: &str) -> Option<String> {
+        self.invalid_element(value).map(|v| format!("{} is not one of the allowed values", v))
+    }
+}
+
+/// One of the keys, mapped to its associated value, e.g. "--speed fast" with ("fast", 3000).
+pub struct MappedEnumParameter<T> {
+    values: Vec<(String, T)>,
+    default_key: String,
+    key: RefCell<String>,
+    value: RefCell<T>,
+}
+
+impl<T: Clone + PartialEq> MappedEnumParameter<T> {
+    /// The default value does not need to be one of the mapped values.
+    pub fn new(values: Vec<(String, T)>, value: T) -> MappedEnumParameter<T> {
+        let default_key = values.iter().find(|(_, v)| *v == value).map(|(key, _)| key.clone()).unwrap_or_default();
+        MappedEnumParameter { values, default_key, key: RefCell::new("".to_string()), value: RefCell::new(value) }
+    }
+
+    pub fn get_value(&self) -> T {
+        self.value.borrow().clone()
+    }
+
+    /// Key given on the command line, empty if the default is used.
+    pub fn get_key(&self) -> String {
+        self.key.borrow().clone()
+    }
+}
+
+impl<T: Clone> ValueHandler for MappedEnumParameter<T> {
+    fn parse_value(&self, value: &str) -> bool {
+        if let Some((key, v)) = self.values.iter().find(|(key, _)| key == value) {
+            *self.key.borrow_mut() = key.clone();
+            *self.value.borrow_mut() = v.clone();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " ".to_string() + &self.values.iter().map(|(key, _)| key.as_str()).collect::<Vec<&str>>().join("|")
+    }
+
+    /// Key given on the command line, otherwise the key mapped to the default value,
+    /// empty if the default is not one of the mapped values.
+    fn value_string(&self) -> String {
+        let key = self.key.borrow();
+        if key.is_empty() { self.default_key.clone() } else { key.clone() }
+    }
+}
+
+/// Accepts one of the keywords (e.g. "stdout", "stderr") or any other value the validator accepts
+/// (e.g. a file name). is_keyword tells which of the two was given.
+pub struct ChoiceOrFreeParameter {
+    keywords: HashSet<String>,
+    validator: fn(&str) -> bool,
+    value: RefCell<String>,
+}
+
+impl ChoiceOrFreeParameter {
+    pub fn new(keywords: Vec<String>, value: &str, validator: fn(&str) -> bool) -> ChoiceOrFreeParameter {
+        ChoiceOrFreeParameter {
+            keywords: keywords.into_iter().collect(),
+            validator,
+            value: RefCell::new(value.to_string()),
+        }
+    }
+
+    pub fn get_value(&self) -> String {
+        self.value.borrow().clone()
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        self.keywords.contains(self.value.borrow().as_str())
+    }
+}
+
+impl ValueHandler for ChoiceOrFreeParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        if self.keywords.contains(value) || (self.validator)(value) {
+            *self.value.borrow_mut() = value.to_string();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        let mut keywords: Vec<&str> = self.keywords.iter().map(|k| k.as_str()).collect();
+        keywords.sort();
+        keywords.push("value");
+        format!(" {}", keywords.join("|"))
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().clone()
+    }
+}
+
+/// Enum parameter with the allowed values read from a file (one per line, empty lines are ignored).
+pub struct FileSetEnumParameter {
+    values: HashSet<String>,
+    value: RefCell<String>,
+}
+
+impl FileSetEnumParameter {
+    pub fn new(file_name: &str, value: &str) -> Result<FileSetEnumParameter, Error> {
+        let contents = fs::read_to_string(file_name)
+            .map_err(|e| Error::new(e.kind(), format!("cannot read allowed values from {}: {}", file_name, e)))?;
+        let values = contents.lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect();
+        Ok(FileSetEnumParameter { values, value: RefCell::new(value.to_string()) })
+    }
+
+    pub fn get_value(&self) -> String {
+        self.value.borrow().clone()
+    }
+}
+
+impl ValueHandler for FileSetEnumParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        if self.values.contains(value) {
+            *self.value.borrow_mut() = value.to_string();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " value".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().clone()
+    }
+}
+
+pub struct BoolParameter {
+    value: Cell<bool>,
+}
+
+impl BoolParameter {
+    pub fn new() -> BoolParameter {
+        BoolParameter { value: Cell::new(false) }
+    }
+
+    pub fn get_value(&self) -> bool {
+        self.value.get()
+    }
+}
+
+impl Default for BoolParameter {
+    fn default() -> Self {
+        BoolParameter::new()
+    }
+}
+
+impl ValueHandler for BoolParameter {
+    fn parse_value(&self, _value: &str) -> bool {
+        // should not be called
+        false
+    }
+
+    fn requires_value(&self) -> bool {
+        false
+    }
+
+    fn set_value(&self) {
+        self.value.set(true);
+    }
+
+    fn value_type(&self) -> String {
+        "".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.get().to_string()
+    }
+}
+
+/// Bool switch that takes a value, e.g. "--cache on". Words are matched case-insensitively,
+/// by default true/yes/on/1 and false/no/off/0 are accepted.
+pub struct ExplicitBoolParameter {
+    value: Cell<bool>,
+    true_words: Vec<String>,
+    false_words: Vec<String>,
+}
+
+impl ExplicitBoolParameter {
+    pub fn new(value: bool) -> ExplicitBoolParameter {
+        ExplicitBoolParameter::with_words(value,
+                                          vec!["true".to_string(), "yes".to_string(), "on".to_string(), "1".to_string()],
+                                          vec!["false".to_string(), "no".to_string(), "off".to_string(), "0".to_string()])
+    }
+
+    /// Same as new, but with custom words, e.g. enabled/disabled.
+    pub fn with_words(value: bool, true_words: Vec<String>, false_words: Vec<String>) -> ExplicitBoolParameter {
+        ExplicitBoolParameter {
+            value: Cell::new(value),
+            true_words: true_words.iter().map(|w| w.to_lowercase()).collect(),
+            false_words: false_words.iter().map(|w| w.to_lowercase()).collect(),
+        }
+    }
+
+    pub fn get_value(&self) -> bool {
+        self.value.get()
+    }
+}
+
+impl ValueHandler for ExplicitBoolParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        let value = value.to_lowercase();
+        if self.true_words.contains(&value) {
+            self.value.set(true);
+        } else if self.false_words.contains(&value) {
+            self.value.set(false);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        format!(" {}|{}", self.true_words.first().map(|w| w.as_str()).unwrap_or_default(),
+                self.false_words.first().map(|w| w.as_str()).unwrap_or_default())
+    }
+
+    fn value_string(&self) -> String {
+        self.value.get().to_string()
+    }
+}
+
+/// Size in bytes with an optional K, M or G suffix, e.g. "512K". Stored as u64, so sizes above isize::MAX
+/// (e.g. 4G on 32-bit targets) are representable; negative sizes are rejected.
+pub struct SizeParameter {
+    value: Cell<u64>,
+    validator: Box<dyn Fn(u64) -> bool>,
+}
+
+impl SizeParameter {
+    /// The validator gets sizes saturated to isize::MAX. Panics if the default value is negative.
+    pub fn new(value: isize, validator: impl Fn(isize) -> bool + 'static) -> SizeParameter {
+        match SizeParameter::try_new(value, validator) {
+            Ok(p) => p,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    /// Same as new, but returns an error if the default value is negative.
+    pub fn try_new(value: isize, validator: impl Fn(isize) -> bool + 'static) -> Result<SizeParameter, Error> {
+        let value = u64::try_from(value).map_err(|_|
+            Error::new(ErrorKind::InvalidInput, format!("size default value {} is negative", value)))?;
+        Ok(SizeParameter::new_u64(value, move |v| validator(isize::try_from(v).unwrap_or(isize::MAX))))
+    }
+
+    pub fn new_u64(value: u64, validator: impl Fn(u64) -> bool + 'static) -> SizeParameter {
+        SizeParameter { value: Cell::new(value), validator: Box::new(validator) }
+    }
+
+    /// Also rejects sizes below min, e.g. SizeParameter::new(1024, |_| true).with_min(512).with_max(1 << 30).
+    /// Panics if the default value is below min.
+    pub fn with_min(self, min: u64) -> SizeParameter {
+        let value = self.value.get();
+        if value < min {
+            panic!("size default value {} is below the minimum {}", value, min);
+        }
+        let validator = self.validator;
+        SizeParameter { value: self.value, validator: Box::new(move |v| v >= min && validator(v)) }
+    }
+
+    /// Also rejects sizes above max. Panics if the default value is above max.
+    pub fn with_max(self, max: u64) -> SizeParameter {
+        let value = self.value.get();
+        if value > max {
+            panic!("size default value {} is above the maximum {}", value, max);
+        }
+        let validator = self.validator;
+        SizeParameter { value: self.value, validator: Box::new(move |v| v <= max && validator(v)) }
+    }
+
+    /// Takes the default from the environment variable (e.g. "512M"). When the variable is not set
+    /// or its value is invalid, the given default is used.
+    pub fn from_env(name: &str, value: isize, validator: impl Fn(isize) -> bool + 'static) -> SizeParameter {
+        let parameter = SizeParameter::new(value, validator);
+        parse_env_default(&parameter, name);
+        parameter
+    }
+
+    /// Size saturated to isize::MAX, use get_value_u64 for sizes that may not fit.
+    pub fn get_value(&self) -> isize {
+        isize::try_from(self.value.get()).unwrap_or(isize::MAX)
+    }
+
+    pub fn get_value_u64(&self) -> u64 {
+        self.value.get()
+    }
+}
+
+/// Parses a size with an optional K, M or G suffix (powers of 1024), e.g. "512M".
+fn parse_size(value: &str) -> Option<u64> {
+    let multiplier = match value.chars().last()? {
+        'M' => 1024 * 1024,
+        'K' => 1024,
+        'G' => 1024 * 1024 * 1024,
+        _ => 1
+    };
+    let size = if multiplier == 1 {
+        u64::from_str(value)
+    } else {
+        let mut chars = value.chars();
+        chars.next_back();
+        u64::from_str(chars.as_str())
+    };
+    size.ok()?.checked_mul(multiplier)
+}
+
+impl ValueHandler for SizeParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        if let Some(size) = parse_size(value) {
+            if (self.validator)(size) {
+                self.value.set(size);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " size".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.get().to_string()
+    }
+}
+
+/// Count with an optional decimal SI suffix, e.g. "--count 1k" for 1000: k, M, G and T are powers of 1000.
+pub struct DecimalScaledParameter {
+    value: Cell<u64>,
+}
+
+impl DecimalScaledParameter {
+    pub fn new(value: u64) -> DecimalScaledParameter {
+        DecimalScaledParameter { value: Cell::new(value) }
+    }
+
+    pub fn get_value(&self) -> u64 {
+        self.value.get()
+    }
+}
+
+impl ValueHandler for DecimalScaledParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        let (number, multiplier) = match value.char_indices().last() {
+            Some((i, 'k')) => (&value[..i], 1_000),
+            Some((i, 'M')) => (&value[..i], 1_000_000),
+            Some((i, 'G')) => (&value[..i], 1_000_000_000),
+            Some((i, 'T')) => (&value[..i], 1_000_000_000_000),
+            _ => (value, 1)
+        };
+        match u64::from_str(number).ok().and_then(|n| n.checked_mul(multiplier)) {
+            Some(v) => {
+                self.value.set(v);
+                true
+            }
+            None => false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " count".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.get().to_string()
+    }
+}
+
+/// Size change in bytes that must have a sign, e.g. "--grow +10G" or "--shrink -2G".
+pub struct SignedSizeParameter {
+    value: Cell<i64>,
+    grow: Cell<bool>,
+}
+
+impl SignedSizeParameter {
+    pub fn new(value: i64) -> SignedSizeParameter {
+        SignedSizeParameter { value: Cell::new(value), grow: Cell::new(value >= 0) }
+    }
+
+    /// Signed byte delta, e.g. -2147483648 for "-2G".
+    pub fn get_value(&self) -> i64 {
+        self.value.get()
+    }
+
+    /// Returns true if the value had a '+' sign.
+    pub fn is_grow(&self) -> bool {
+        self.grow.get()
+    }
+}
+
+impl ValueHandler for SignedSizeParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        let (grow, size) = if let Some(size) = value.strip_prefix('+') {
+            (true, size)
+        } else if let Some(size) = value.strip_prefix('-') {
+            (false, size)
+        } else {
+            return false;
+        };
+        match parse_size(size).and_then(|s| i64::try_from(s).ok()) {
+            Some(size) => {
+                self.value.set(if grow { size } else { -size });
+                self.grow.set(grow);
+                true
+            }
+            None => false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " +size|-size".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        let value = self.value.get();
+        if self.grow.get() { format!("+{}", value) } else { value.to_string() }
+    }
+}
+
+/// Number that is either relative to some base when it has a sign ("+5", "-3") or absolute ("5").
+pub struct SignedAdjustmentParameter {
+    value: Cell<isize>,
+    relative: Cell<bool>,
+}
+
+impl SignedAdjustmentParameter {
+    pub fn new(value: isize) -> SignedAdjustmentParameter {
+        SignedAdjustmentParameter { value: Cell::new(value), relative: Cell::new(false) }
+    }
+
+    /// The delta for a relative value, the value itself otherwise.
+    pub fn get_value(&self) -> isize {
+        self.value.get()
+    }
+
+    pub fn is_relative(&self) -> bool {
+        self.relative.get()
+    }
+
+    /// Adds a relative value to the base, saturating at isize::MIN and isize::MAX,
+    /// returns an absolute value as is.
+    pub fn apply(&self, base: isize) -> isize {
+        if self.relative.get() {
+            base.saturating_add(self.value.get())
+        } else {
+            self.value.get()
+        }
+    }
+}
+
+impl ValueHandler for SignedAdjustmentParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        if let Ok(v) = isize::from_str(value) {
+            self.value.set(v);
+            self.relative.set(value.starts_with(['+', '-']));
+            return true;
+        }
+        false
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " [+|-]int".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        if self.relative.get() {
+            format!("{:+}", self.value.get())
+        } else {
+            self.value.get().to_string()
+        }
+    }
+
+    fn error_kind(&self, value: &str) -> ParseErrorKind {
+        int_error_kind(value)
+    }
+}
+
+/// Memory address in hex with a required "0x" prefix, e.g. "0x7fff1234".
+pub struct PointerParameter {
+    value: Cell<usize>,
+}
+
+impl PointerParameter {
+    pub fn new(value: usize) -> PointerParameter {
+        PointerParameter { value: Cell::new(value) }
+    }
+
+    pub fn get_value(&self) -> usize {
+        self.value.get()
+    }
+}
+
+impl ValueHandler for PointerParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        if let Some(hex) = value.strip_prefix("0x") {
+            // from_str_radix accepts a leading '+', so check the digits first
+            if hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                if let Ok(v) = usize::from_str_radix(hex, 16) {
+                    self.value.set(v);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " address".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        format!("{:#x}", self.value.get())
+    }
+}
+
+/// Temperature with a C, F or K suffix (e.g. "100C", "212F", "373.15K"), stored in Celsius.
+pub struct TemperatureParameter {
+    value: Cell<f64>,
+}
+
+impl TemperatureParameter {
+    pub fn new(value: f64) -> TemperatureParameter {
+        TemperatureParameter { value: Cell::new(value) }
+    }
+
+    /// Temperature in Celsius.
+    pub fn get_value(&self) -> f64 {
+        self.value.get()
+    }
+}
+
+impl ValueHandler for TemperatureParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        let mut chars = value.chars();
+        let convert: fn(f64) -> f64 = match chars.next_back() {
+            Some('C') => |v| v,
+            Some('F') => |v| (v - 32.0) * 5.0 / 9.0,
+            Some('K') => |v| v - 273.15,
+            _ => return false
+        };
+        if let Ok(v) = f64::from_str(chars.as_str()) {
+            let celsius = convert(v);
+            if celsius.is_finite() && celsius >= -273.15 {
+                self.value.set(celsius);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " temperature".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        format!("{}C", self.value.get())
+    }
+}
+
+/// ISO 8601 duration, e.g. "PT1H30M", "PT45S" or "P1DT12H". Weeks and days are supported in the date part,
+/// years and months are rejected because their length varies. Seconds may have a fraction ("PT0.5S").
+pub struct IsoDurationParameter {
+    value: Cell<Duration>,
+}
+
+impl IsoDurationParameter {
+    pub fn new(value: Duration) -> IsoDurationParameter {
+        IsoDurationParameter { value: Cell::new(value) }
+    }
+
+    pub fn get_value(&self) -> Duration {
+        self.value.get()
+    }
+}
+
+/// Sums "<number><designator>" components, the designators must follow the order of units.
+fn parse_duration_components(value: &str, units: &[(char, f64)]) -> Option<f64> {
+    let mut seconds = 0.0;
+    let mut next_unit = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let idx = units[next_unit..].iter().position(|(designator, _)| *designator == c)? + next_unit;
+        seconds += f64::from_str(&number).ok()? * units[idx].1;
+        number.clear();
+        next_unit = idx + 1;
+    }
+    if number.is_empty() { Some(seconds) } else { None }
+}
+
+fn parse_iso_duration(value: &str) -> Option<Duration> {
+    let value = value.strip_prefix('P')?;
+    let (date, time) = match value.split_once('T') {
+        Some((_, "")) => return None,
+        Some((date, time)) => (date, time),
+        None if value.is_empty() => return None,
+        None => (value, "")
+    };
+    let seconds = parse_duration_components(date, &[('W', 604800.0), ('D', 86400.0)])?
+        + parse_duration_components(time, &[('H', 3600.0), ('M', 60.0), ('S', 1.0)])?;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+impl ValueHandler for IsoDurationParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        if let Some(duration) = parse_iso_duration(value) {
+            self.value.set(duration);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " duration".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        format!("PT{}S", self.value.get().as_secs_f64())
+    }
+}
+
+/// Parameter without a default: try_get_value is None until a value is parsed.
+pub struct OptionalParameter<T> {
+    value: RefCell<Option<T>>,
+}
+
+impl<T: FromStr + Clone + ToString> OptionalParameter<T> {
+    pub fn new() -> OptionalParameter<T> {
+        OptionalParameter { value: RefCell::new(None) }
+    }
+
+    pub fn try_get_value(&self) -> Option<T> {
+        self.value.borrow().clone()
+    }
+}
+
+impl<T: FromStr + Clone + ToString> Default for OptionalParameter<T> {
+    fn default() -> Self {
+        OptionalParameter::new()
+    }
+}
+
+impl<T: FromStr + Clone + ToString> ValueHandler for OptionalParameter<T> {
+    fn parse_value(&self, value: &str) -> bool {
+        if let Ok(v) = T::from_str(value) {
+            *self.value.borrow_mut() = Some(v);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " value".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().as_ref().map(|v| v.to_string()).unwrap_or_default()
+    }
+}
+
+/// Switch that takes a fixed number of values, e.g. "--point 1 2 3".
+pub struct NaryParameter<T> {
+    count: usize,
+    values: RefCell<Vec<T>>,
+}
+
+impl<T: FromStr + Clone + ToString> NaryParameter<T> {
+    pub fn new(count: usize, values: Vec<T>) -> NaryParameter<T> {
+        NaryParameter { count, values: RefCell::new(values) }
+    }
+
+    pub fn get_values(&self) -> Vec<T> {
+        self.values.borrow().clone()
+    }
+}
+
+impl<T: FromStr + Clone + ToString> ValueHandler for NaryParameter<T> {
+    fn parse_value(&self, value: &str) -> bool {
+        self.parse_values(&[value])
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " value".repeat(self.count)
+    }
+
+    fn value_string(&self) -> String {
+        self.values.borrow().iter().map(|v| v.to_string()).collect::<Vec<String>>().join(" ")
+    }
+
+    fn value_count(&self) -> usize {
+        self.count
+    }
+
+    fn parse_values(&self, values: &[&str]) -> bool {
+        if values.len() != self.count {
+            return false;
+        }
+        let parsed: Result<Vec<T>, _> = values.iter().map(|v| T::from_str(v)).collect();
+        if let Ok(parsed) = parsed {
+            *self.values.borrow_mut() = parsed;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Comma-separated list with an exact number of elements, e.g. "--coords 1,2,3".
+pub struct FixedCsvParameter<T> {
+    count: usize,
+    values: RefCell<Vec<T>>,
+}
+
+impl<T: FromStr + Clone + ToString> FixedCsvParameter<T> {
+    pub fn new(count: usize, values: Vec<T>) -> FixedCsvParameter<T> {
+        FixedCsvParameter { count, values: RefCell::new(values) }
+    }
+
+    pub fn get_values(&self) -> Vec<T> {
+        self.values.borrow().clone()
+    }
+}
+
+impl<T: FromStr + Clone + ToString> ValueHandler for FixedCsvParameter<T> {
+    fn parse_value(&self, value: &str) -> bool {
+        let parts: Vec<&str> = value.split(',').map(|v| v.trim()).collect();
+        if parts.len() != self.count {
+            return false;
+        }
+        let parsed: Result<Vec<T>, _> = parts.iter().map(|v| T::from_str(v)).collect();
+        if let Ok(parsed) = parsed {
+            *self.values.borrow_mut() = parsed;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        format!(" list[{}]", self.count)
+    }
+
+    fn value_string(&self) -> String {
+        self.values.borrow().iter().map(|v| v.to_string()).collect::<Vec<String>>().join(",")
+    }
+}
+
+/// Comma-separated list where values in double quotes may contain commas, e.g. --names '"Doe, John",Alice'.
+/// Inside quotes "" stands for a literal quote.
+pub struct QuotedCsvParameter {
+    values: RefCell<Vec<String>>,
+}
+
+impl QuotedCsvParameter {
+    pub fn new(values: Vec<String>) -> QuotedCsvParameter {
+        QuotedCsvParameter { values: RefCell::new(values) }
+    }
+
+    pub fn get_values(&self) -> Vec<String> {
+        self.values.borrow().clone()
+    }
+}
+
+/// Returns None for an unterminated quote.
+fn parse_quoted_csv(value: &str) -> Option<Vec<String>> {
+    let mut values = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => loop {
+                match chars.next()? {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        current.push('"');
+                    }
+                    '"' => break,
+                    c => current.push(c)
+                }
+            },
+            ',' => values.push(std::mem::take(&mut current)),
+            c => current.push(c)
+        }
+    }
+    values.push(current);
+    Some(values)
+}
+
+impl ValueHandler for QuotedCsvParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        if let Some(values) = parse_quoted_csv(value) {
+            *self.values.borrow_mut() = values;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " list".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.values.borrow().iter()
+            .map(|v| if v.contains([',', '"']) { format!("\"{}\"", v.replace('"', "\"\"")) } else { v.clone() })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+/// Counts how many times a flag was given, e.g. -vvv for verbosity level 3. Each Arguments::build counts from 0.
+/// With a maximum, extra occurrences are ignored, or fail Arguments::build after error_on_overflow.
+pub struct CountParameter {
+    value: Cell<usize>,
+    max: Option<usize>,
+    error_on_overflow: bool,
+    overflow: Cell<bool>,
+}
+
+impl CountParameter {
+    pub fn new(max: Option<usize>) -> CountParameter {
+        CountParameter { value: Cell::new(0), max, error_on_overflow: false, overflow: Cell::new(false) }
+    }
+
+    pub fn error_on_overflow(mut self) -> CountParameter {
+        self.error_on_overflow = true;
+        self
+    }
+
+    pub fn get_value(&self) -> usize {
+        self.value.get()
+    }
+
+    /// Turns the count into a final value, e.g. map(|n| base << n) for a buffer size doubled by each -B.
+    pub fn map<R>(&self, f: impl Fn(usize) -> R) -> R {
+        f(self.value.get())
+    }
+}
+
+impl ValueHandler for CountParameter {
+    fn parse_value(&self, _value: &str) -> bool {
+        // should not be called
+        false
+    }
+
+    fn requires_value(&self) -> bool {
+        false
+    }
+
+    fn set_value(&self) {
+        let value = self.value.get();
+        if self.max.is_some_and(|max| value >= max) {
+            self.overflow.set(true);
+        } else {
+            self.value.set(value + 1);
+        }
+    }
+
+    fn value_type(&self) -> String {
+        "".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.get().to_string()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.error_on_overflow && self.overflow.get() {
+            Err(format!("switch can be used at most {} times", self.max.unwrap_or_default()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn reset(&self) {
+        self.value.set(0);
+        self.overflow.set(false);
+    }
+}
+
+/// Reads a secret: gets the prompt, returns the entered value.
+pub type SecretReader = Box<dyn Fn(&str) -> io::Result<String>>;
+
+/// Flag that prompts for a value (e.g. a password) instead of taking it from the command line,
+/// so it doesn't end up in the shell history. The default reader disables terminal echo with stty.
+pub struct SecretParameter {
+    prompt: String,
+    reader: SecretReader,
+    value: RefCell<String>,
+    error: RefCell<Option<String>>,
+}
+
+impl SecretParameter {
+    pub fn new(prompt: &str) -> SecretParameter {
+        SecretParameter::with_reader(prompt, read_secret_from_terminal)
+    }
+
+    /// Same as new, but with a custom reader that gets the prompt and returns the secret.
+    pub fn with_reader(prompt: &str, reader: impl Fn(&str) -> io::Result<String> + 'static) -> SecretParameter {
+        SecretParameter {
+            prompt: prompt.to_string(),
+            reader: Box::new(reader),
+            value: RefCell::new("".to_string()),
+            error: RefCell::new(None),
+        }
+    }
+
+    pub fn get_value(&self) -> String {
+        self.value.borrow().clone()
+    }
+}
+
+fn read_secret_from_terminal(prompt: &str) -> io::Result<String> {
+    eprint!("{}", prompt);
+    let stty = |arg: &str| Command::new("stty").arg(arg).stdin(Stdio::inherit()).status()
+        .map(|s| s.success()).unwrap_or(false);
+    let echo_disabled = cfg!(unix) && io::stdin().is_terminal() && stty("-echo");
+    let mut line = String::new();
+    let result = io::stdin().read_line(&mut line);
+    if echo_disabled {
+        stty("echo");
+        eprintln!();
+    }
+    result?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+impl ValueHandler for SecretParameter {
+    fn parse_value(&self, _value: &str) -> bool {
+        // should not be called
+        false
+    }
+
+    fn requires_value(&self) -> bool {
+        false
+    }
+
+    fn set_value(&self) {
+        match (self.reader)(&self.prompt) {
+            Ok(value) => {
+                *self.value.borrow_mut() = value;
+                *self.error.borrow_mut() = None;
+            }
+            Err(e) => *self.error.borrow_mut() = Some(e.to_string())
+        }
+    }
+
+    fn value_type(&self) -> String {
+        "".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        "*****".to_string()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self.error.borrow().as_ref() {
+            Some(e) => Err(format!("secret read error: {}", e)),
+            None => Ok(())
+        }
+    }
+
+    fn reset(&self) {
+        *self.error.borrow_mut() = None;
+    }
+}
+
+/// String switch that reads the whole stdin when the value is "-", e.g. "--data -".
+pub struct StdinOrValueStringParameter {
+    reader: Box<dyn Fn() -> io::Result<String>>,
+    value: RefCell<String>,
+}
+
+impl StdinOrValueStringParameter {
+    pub fn new(value: &str) -> StdinOrValueStringParameter {
+        StdinOrValueStringParameter::with_reader(value, || io::read_to_string(io::stdin()))
+    }
+
+    /// Same as new, but with a custom reader used instead of stdin.
+    pub fn with_reader(value: &str, reader: impl Fn() -> io::Result<String> + 'static) -> StdinOrValueStringParameter {
+        StdinOrValueStringParameter { reader: Box::new(reader), value: RefCell::new(value.to_string()) }
+    }
+
+    pub fn get_value(&self) -> String {
+        self.value.borrow().clone()
+    }
+}
+
+impl ValueHandler for StdinOrValueStringParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        let value = if value == "-" {
+            match (self.reader)() {
+                Ok(v) => v,
+                Err(_) => return false
+            }
+        } else {
+            value.to_string()
+        };
+        *self.value.borrow_mut() = value;
+        true
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " string".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.value.borrow().clone()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToggleMode {
+    /// The last switch on the command line wins.
+    LastWins,
+    /// Using both switches of the group fails Arguments::build.
+    ErrorOnConflict,
+}
+
+/// Links a pair of flags (like --verbose/--quiet) to a single state:
+/// 1 when the "on" switch was used, -1 for the "off" switch and 0 when neither was used.
+pub struct ToggleGroup {
+    state: Cell<i8>,
+    mode: ToggleMode,
+    // state set in the current build call, 0 when neither switch was used yet
+    seen: Cell<i8>,
+    conflict: Cell<bool>,
+}
+
+impl ToggleGroup {
+    pub fn new(mode: ToggleMode) -> ToggleGroup {
+        ToggleGroup { state: Cell::new(0), mode, seen: Cell::new(0), conflict: Cell::new(false) }
+    }
+
+    pub fn get_value(&self) -> i8 {
+        self.state.get()
+    }
+
+    /// Handler for the switch that sets the state to 1.
+    pub fn on(&self) -> ToggleSwitch<'_> {
+        ToggleSwitch { group: self, state: 1 }
+    }
+
+    /// Handler for the switch that sets the state to -1.
+    pub fn off(&self) -> ToggleSwitch<'_> {
+        ToggleSwitch { group: self, state: -1 }
+    }
+}
+
+pub struct ToggleSwitch<'a> {
+    group: &'a ToggleGroup,
+    state: i8,
+}
+
+impl ValueHandler for ToggleSwitch<'_> {
+    fn parse_value(&self, _value: &str) -> bool {
+        // should not be called
+        false
+    }
+
+    fn requires_value(&self) -> bool {
+        false
+    }
+
+    fn set_value(&self) {
+        let seen = self.group.seen.get();
+        if self.group.mode == ToggleMode::ErrorOnConflict && seen != 0 && seen != self.state {
+            self.group.conflict.set(true);
+        }
+        self.group.seen.set(self.state);
+        self.group.state.set(self.state);
+    }
+
+    fn value_type(&self) -> String {
+        "".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        (self.group.state.get() == self.state).to_string()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.group.conflict.get() {
+            Err("conflicting switches".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn reset(&self) {
+        self.group.seen.set(0);
+        self.group.conflict.set(false);
+    }
+}
+
+/// Flag that works as a preset for another parameter, e.g. --fast for "--level 9":
+/// using the flag passes the fixed value to the target's parse_value.
+pub struct AliasSwitch<'a> {
+    target: &'a dyn ValueHandler,
+    value: String,
+    used: Cell<bool>,
+    failed: Cell<bool>,
+}
+
+impl<'a> AliasSwitch<'a> {
+    pub fn new(target: &'a dyn ValueHandler, value: &str) -> AliasSwitch<'a> {
+        AliasSwitch { target, value: value.to_string(), used: Cell::new(false), failed: Cell::new(false) }
+    }
+
+    /// True if the flag was used by the last Arguments::build.
+    pub fn get_value(&self) -> bool {
+        self.used.get()
+    }
+}
+
+impl ValueHandler for AliasSwitch<'_> {
+    fn parse_value(&self, _value: &str) -> bool {
+        // should not be called
+        false
+    }
+
+    fn requires_value(&self) -> bool {
+        false
+    }
+
+    fn set_value(&self) {
+        self.used.set(true);
+        self.failed.set(!self.target.parse_value(&self.value));
+    }
+
+    fn value_type(&self) -> String {
+        "".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.used.get().to_string()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.failed.get() {
+            Err(format!("invalid alias value {}", self.value))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn reset(&self) {
+        self.used.set(false);
+        self.failed.set(false);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    InvalidValue,
+    NotANumber,
+    ValueOutOfRange,
+    ValueExpected,
+    UnexpectedValue,
+    UnknownSwitch,
+    InvalidSwitch,
+    IncorrectArgumentCount,
+    CommandExpected,
+    UnknownCommand,
+    UnterminatedQuote,
+    NoGlobMatch,
+    RequiredSwitchMissing,
+    ValidationFailed,
+}
+
+impl ParseErrorKind {
+    /// Stable snake_case name of the kind, e.g. "unknown_switch".
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParseErrorKind::InvalidValue => "invalid_value",
+            ParseErrorKind::NotANumber => "not_a_number",
+            ParseErrorKind::ValueOutOfRange => "value_out_of_range",
+            ParseErrorKind::ValueExpected => "value_expected",
+            ParseErrorKind::UnexpectedValue => "unexpected_value",
+            ParseErrorKind::UnknownSwitch => "unknown_switch",
+            ParseErrorKind::InvalidSwitch => "invalid_switch",
+            ParseErrorKind::IncorrectArgumentCount => "incorrect_argument_count",
+            ParseErrorKind::CommandExpected => "command_expected",
+            ParseErrorKind::UnknownCommand => "unknown_command",
+            ParseErrorKind::UnterminatedQuote => "unterminated_quote",
+            ParseErrorKind::NoGlobMatch => "no_glob_match",
+            ParseErrorKind::RequiredSwitchMissing => "required_switch_missing",
+            ParseErrorKind::ValidationFailed => "validation_failed",
+        }
+    }
+}
+
+/// Error returned by Arguments::build, wrapped into io::Error with ErrorKind::InvalidInput.
+/// Use ParseError::from_error to get it back from the io::Error.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    switch: Option<String>,
+    message: String,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, switch: Option<&str>, message: &str) -> ParseError {
+        ParseError { kind, switch: switch.map(|s| s.to_string()), message: message.to_string() }
+    }
+
+    pub fn from_error(error: &Error) -> Option<&ParseError> {
+        error.get_ref().and_then(|e| e.downcast_ref::<ParseError>())
+    }
+
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// Switch (or switch name) the error refers to, if any.
+    pub fn switch(&self) -> Option<&str> {
+        self.switch.as_deref()
+    }
+
+    /// Machine-readable form of the error, e.g. {"kind":"unknown_switch","message":"unknown switch","switch":"-z"}.
+    /// The switch field is null when the error does not refer to a switch.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::json!({"kind": self.kind.name(), "switch": self.switch, "message": self.message}).to_string()
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::new(ErrorKind::InvalidInput, error)
+    }
+}
+
+/// Where the effective value of a switch came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueSource {
+    /// The switch was not given, the handler keeps its default.
+    Default,
+    /// Set from an environment variable by Arguments::apply_env.
+    Env,
+    /// Given on the command line.
+    Cli,
+}
+
+/// Non-fatal condition detected during Arguments::build (for example, a deprecated switch was used).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    switch_name: String,
+    message: String,
+    replacement: Option<String>,
+}
+
+impl Warning {
+    pub fn new(switch_name: &str, message: &str) -> Warning {
+        Warning { switch_name: switch_name.to_string(), message: message.to_string(), replacement: None }
+    }
+
+    pub fn with_replacement(switch_name: &str, message: &str, replacement: &str) -> Warning {
+        Warning { switch_name: switch_name.to_string(), message: message.to_string(),
+            replacement: Some(replacement.to_string()) }
+    }
+
+    pub fn get_switch_name(&self) -> &str {
+        &self.switch_name
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    /// Switch that should be used instead of the deprecated one, if known.
+    pub fn get_replacement(&self) -> Option<&str> {
+        self.replacement.as_deref()
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.switch_name, self.message)
+    }
+}
+
+/// List of paths separated like in PATH (':' on Unix, ';' on Windows), e.g. "--libpath /a:/b:/c".
+pub struct PathListParameter {
+    values: RefCell<Vec<PathBuf>>,
+}
+
+impl PathListParameter {
+    pub fn new(values: Vec<PathBuf>) -> PathListParameter {
+        PathListParameter { values: RefCell::new(values) }
+    }
+
+    pub fn get_values(&self) -> Vec<PathBuf> {
+        self.values.borrow().clone()
+    }
+}
+
+impl ValueHandler for PathListParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        *self.values.borrow_mut() = std::env::split_paths(value).collect();
+        true
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " paths".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        std::env::join_paths(self.values.borrow().iter())
+            .map(|paths| paths.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Parses comma-separated integers and inclusive ranges, e.g. "0-3,5,7-8", into a sorted list
+/// without duplicates. Negative numbers are not supported, lists longer than MAX_RANGE_SET_LEN values
+/// are rejected.
+pub struct RangeSetParameter {
+    values: RefCell<Vec<isize>>,
+}
+
+impl RangeSetParameter {
+    pub fn new(values: Vec<isize>) -> RangeSetParameter {
+        RangeSetParameter { values: RefCell::new(values) }
+    }
+
+    pub fn get_values(&self) -> Vec<isize> {
+        self.values.borrow().clone()
+    }
+}
+
+/// Limit for the values of a RangeSetParameter before removing duplicates, so that a range like "0-999999999999"
+/// is rejected instead of exhausting memory.
+pub const MAX_RANGE_SET_LEN: usize = 65536;
+
+fn parse_range_set(value: &str) -> Option<Vec<isize>> {
+    let mut values = Vec::new();
+    for token in value.split(',') {
+        if let Some((from, to)) = token.split_once('-') {
+            let from = isize::from_str(from).ok()?;
+            let to = isize::from_str(to).ok()?;
+            if from > to || to.abs_diff(from) >= MAX_RANGE_SET_LEN - values.len() {
+                return None;
+            }
+            values.extend(from..=to);
+        } else {
+            values.push(isize::from_str(token).ok()?);
+        }
+        if values.len() > MAX_RANGE_SET_LEN {
+            return None;
+        }
+    }
+    values.sort();
+    values.dedup();
+    Some(values)
+}
+
+impl ValueHandler for RangeSetParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        if let Some(values) = parse_range_set(value) {
+            *self.values.borrow_mut() = values;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " ranges".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.values.borrow().iter().map(|v| v.to_string()).collect::<Vec<String>>().join(",")
+    }
+}
+
+/// Key-value switch that can be repeated, e.g. "--set a=1 --set b=2". A later value for the same key
+/// overrides the earlier one.
+pub struct LastWinsKeyValueParameter {
+    values: RefCell<HashMap<String, String>>,
+}
+
+impl LastWinsKeyValueParameter {
+    pub fn new(values: HashMap<String, String>) -> LastWinsKeyValueParameter {
+        LastWinsKeyValueParameter { values: RefCell::new(values) }
+    }
+
+    /// Seeds the map with the environment variables starting with the prefix, with the prefix stripped,
+    /// e.g. from_env_prefix("APP_", HashMap::new()) maps APP_HOST=x to HOST=x. Values given on the command line
+    /// override them.
+    pub fn from_env_prefix(prefix: &str, mut values: HashMap<String, String>) -> LastWinsKeyValueParameter {
+        for (key, value) in std::env::vars() {
+            if let Some(key) = key.strip_prefix(prefix).filter(|k| !k.is_empty()) {
+                values.insert(key.to_string(), value);
+            }
+        }
+        LastWinsKeyValueParameter::new(values)
+    }
+
+    pub fn get_values(&self) -> HashMap<String, String> {
+        self.values.borrow().clone()
+    }
+}
+
+impl ValueHandler for LastWinsKeyValueParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        match value.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                self.values.borrow_mut().insert(key.to_string(), value.to_string());
+                true
+            }
+            _ => false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " key=value".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        let mut values: Vec<String> = self.values.borrow().iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        values.sort();
+        values.join(",")
+    }
+}
+
+/// Same as LastWinsKeyValueParameter, but the key and the value are separated by the first ':',
+/// e.g. "--header Content-Type:application/json".
+pub struct ColonKeyValueParameter {
+    values: RefCell<HashMap<String, String>>,
+}
+
+impl ColonKeyValueParameter {
+    pub fn new(values: HashMap<String, String>) -> ColonKeyValueParameter {
+        ColonKeyValueParameter { values: RefCell::new(values) }
+    }
+
+    pub fn get_values(&self) -> HashMap<String, String> {
+        self.values.borrow().clone()
+    }
+}
+
+impl ValueHandler for ColonKeyValueParameter {
+    fn parse_value(&self, value: &str) -> bool {
+        match value.split_once(':') {
+            Some((key, value)) if !key.is_empty() => {
+                self.values.borrow_mut().insert(key.to_string(), value.to_string());
+                true
+            }
+            _ => false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " key:value".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        let mut values: Vec<String> = self.values.borrow().iter().map(|(k, v)| format!("{}:{}", k, v)).collect();
+        values.sort();
+        values.join(",")
+    }
+}
+
+/// Parses comma-separated key=value pairs, e.g. "--opts a=1,b=2", into a user struct with serde.
+/// Values that are JSON literals (numbers, true, false, null) are passed as such, anything else as a string.
+#[cfg(feature = "serde")]
+pub struct StructParameter<T> {
+    value: RefCell<Option<T>>,
+    raw: RefCell<String>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::de::DeserializeOwned + Clone> StructParameter<T> {
+    pub fn new(value: Option<T>) -> StructParameter<T> {
+        StructParameter { value: RefCell::new(value), raw: RefCell::new("".to_string()) }
+    }
+
+    pub fn get_value(&self) -> Option<T> {
+        self.value.borrow().clone()
+    }
+}
+
+#[cfg(feature = "serde")]
+fn parse_struct<T: serde::de::DeserializeOwned>(value: &str) -> Option<T> {
+    let mut map = serde_json::Map::new();
+    if !value.is_empty() {
+        for pair in value.split(',') {
+            let (key, value) = pair.split_once('=')?;
+            let value = serde_json::from_str(value).ok()
+                .filter(|v: &serde_json::Value| !v.is_array() && !v.is_object() && !v.is_string())
+                .unwrap_or_else(|| serde_json::Value::String(value.to_string()));
+            map.insert(key.to_string(), value);
+        }
+    }
+    serde_json::from_value(serde_json::Value::Object(map)).ok()
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::de::DeserializeOwned + Clone> ValueHandler for StructParameter<T> {
+    fn parse_value(&self, value: &str) -> bool {
+        if let Some(v) = parse_struct(value) {
+            *self.value.borrow_mut() = Some(v);
+            *self.raw.borrow_mut() = value.to_string();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " key=value,...".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.raw.borrow().clone()
+    }
+}
+
+/// Reads and deserializes a JSON file when the switch is parsed, e.g. "--rules rules.json",
+/// so a missing or invalid file fails Arguments::build.
+#[cfg(feature = "serde")]
+pub struct JsonFileParameter<T> {
+    value: RefCell<Option<T>>,
+    file_name: RefCell<String>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::de::DeserializeOwned + Clone> JsonFileParameter<T> {
+    pub fn new(value: Option<T>) -> JsonFileParameter<T> {
+        JsonFileParameter { value: RefCell::new(value), file_name: RefCell::new("".to_string()) }
+    }
+
+    pub fn get_value(&self) -> Option<T> {
+        self.value.borrow().clone()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::de::DeserializeOwned + Clone> ValueHandler for JsonFileParameter<T> {
+    fn parse_value(&self, value: &str) -> bool {
+        let parsed = fs::read_to_string(value).ok().and_then(|contents| serde_json::from_str(&contents).ok());
+        if let Some(v) = parsed {
+            *self.value.borrow_mut() = Some(v);
+            *self.file_name.borrow_mut() = value.to_string();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        true
+    }
+
+    fn set_value(&self) {
+    }
+
+    fn value_type(&self) -> String {
+        " file".to_string()
+    }
+
+    fn value_string(&self) -> String {
+        self.file_name.borrow().clone()
+    }
+}
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+
+fn paint(text: &str, color: &str, colored: bool) -> String {
+    if colored && !text.is_empty() {
+        format!("{}{}\x1b[0m", color, text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn check_short_switch(switch: char) -> Result<(), Error> {
+    if switch.is_ascii_alphanumeric() {
+        Ok(())
+    } else {
+        Err(Error::new(ErrorKind::InvalidInput, format!("invalid short switch {:?}", switch)))
+    }
+}
+
+fn check_ext_switch(ext_switch: &str) -> Result<(), Error> {
+    if !ext_switch.is_empty() && !ext_switch.starts_with('-')
+        && ext_switch.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(())
+    } else {
+        Err(Error::new(ErrorKind::InvalidInput, format!("invalid ext switch {:?}", ext_switch)))
+    }
+}
+
+/// Handler of a switch, borrowed (Switch::new) or owned by the switch (Switch::new_boxed,
+/// Switch::new_typed). Handlers from new_typed can also be downcast by Arguments::handler.
+#[derive(Clone)]
+enum SwitchHandler<'a> {
+    Borrowed(&'a dyn ValueHandler),
+    Owned(Rc<dyn ValueHandler + 'a>, Option<Rc<dyn Any>>),
+}
+
+impl<'a> std::ops::Deref for SwitchHandler<'a> {
+    type Target = dyn ValueHandler + 'a;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            SwitchHandler::Borrowed(handler) => *handler,
+            SwitchHandler::Owned(handler, _) => handler.as_ref()
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Switch<'a> {
+    name: String,
+    switch: Option<char>,
+    ext_switch: Option<String>,
+    handler: SwitchHandler<'a>,
+    deprecated: Option<String>,
+    replacement: Option<String>,
+    category: Option<String>,
+    takes_value: Option<bool>,
+    description: Option<String>,
+    example: Option<String>,
+    hidden: bool,
+    required: bool,
+    transform: Option<fn(&str) -> String>,
+    env: Option<String>,
+    captures_rest: bool,
+}
+
+impl<'a> Switch<'a> {
+    /// Panics if the short switch is not an ASCII letter or digit or the long switch has characters
+    /// other than ASCII letters, digits, '-' and '_' (or starts with '-').
+    pub fn new(name: &str, switch: Option<char>, ext_switch: Option<&str>, handler: &'a dyn ValueHandler) -> Switch<'a> {
+        Switch::new_owned(name, switch, ext_switch.map(|s|s.to_string()), handler)
+    }
+
+    /// Same as new, but returns an error for an invalid short or long switch.
+    pub fn try_new(name: &str, switch: Option<char>, ext_switch: Option<&str>, handler: &'a dyn ValueHandler)
+        -> Result<Switch<'a>, Error> {
+        if let Some(sw) = switch {
+            check_short_switch(sw)?;
+        }
+        if let Some(sw) = ext_switch {
+            check_ext_switch(sw)?;
+        }
+        Ok(Switch::new(name, switch, ext_switch, handler))
+    }
+
+    /// Same as new, but takes an owned long switch, e.g. one built with format!.
+    pub fn new_owned(name: &str, switch: Option<char>, ext_switch: Option<String>, handler: &'a dyn ValueHandler)
+        -> Switch<'a> {
+        Switch::with_handler(name, switch, ext_switch, SwitchHandler::Borrowed(handler))
+    }
+
+    /// Same as new, but the switch owns the handler, e.g. for switches built in a loop.
+    /// Read the values with Arguments::get_value, which only works for handlers whose value_string parses back
+    /// (not for SecretParameter, TemperatureParameter and the like), otherwise use new_typed.
+    pub fn new_boxed(name: &str, switch: Option<char>, ext_switch: Option<&str>, handler: Box<dyn ValueHandler + 'a>)
+        -> Switch<'a> {
+        let handler = SwitchHandler::Owned(Rc::from(handler), None);
+        Switch::with_handler(name, switch, ext_switch.map(|s| s.to_string()), handler)
+    }
+
+    /// Same as new_boxed, but the handler can be read back with its type by Arguments::handler,
+    /// e.g. arguments.handler::<TemperatureParameter>("temp").map(|t| t.get_value()).
+    pub fn new_typed<H: ValueHandler + 'static>(name: &str, switch: Option<char>, ext_switch: Option<&str>, handler: H)
+        -> Switch<'a> {
+        let handler = Rc::new(handler);
+        let owned = SwitchHandler::Owned(handler.clone(), Some(handler));
+        Switch::with_handler(name, switch, ext_switch.map(|s| s.to_string()), owned)
+    }
+
+    fn with_handler(name: &str, switch: Option<char>, ext_switch: Option<String>, handler: SwitchHandler<'a>)
+        -> Switch<'a> {
+        if let Some(Err(e)) = switch.map(check_short_switch) {
+            panic!("{}", e);
+        }
+        if let Some(Err(e)) = ext_switch.as_deref().map(check_ext_switch) {
+            panic!("{}", e);
+        }
+        Switch {
+            name: name.to_string(),
+            switch,
+            ext_switch,
+            handler,
+            deprecated: None,
+            replacement: None,
+            category: None,
+            takes_value: None,
+            description: None,
+            example: None,
+            hidden: false,
+            required: false,
+            transform: None,
+            env: None,
+            captures_rest: false,
+        }
+    }
+
+    /// Switch without short and long switches, set them with short and long,
+    /// e.g. Switch::for_handler("port", &port_parameter).short('p').long("port").
+    pub fn for_handler(name: &str, handler: &'a dyn ValueHandler) -> Switch<'a> {
+        Switch::new_owned(name, None, None, handler)
+    }
+
+    /// Panics if the short switch is not an ASCII letter or digit.
+    pub fn short(mut self, switch: char) -> Switch<'a> {
+        if let Err(e) = check_short_switch(switch) {
+            panic!("{}", e);
+        }
+        self.switch = Some(switch);
+        self
+    }
+
+    /// Panics if the long switch has characters other than ASCII letters, digits, '-' and '_'
+    /// (or starts with '-').
+    pub fn long(mut self, ext_switch: &str) -> Switch<'a> {
+        if let Err(e) = check_ext_switch(ext_switch) {
+            panic!("{}", e);
+        }
+        self.ext_switch = Some(ext_switch.to_string());
+        self
+    }
+
+    /// Marks the switch as deprecated: it is still parsed, but Arguments::build records a warning with the given message.
+    pub fn deprecated(mut self, message: &str) -> Switch<'a> {
+        self.deprecated = Some(message.to_string());
+        self
+    }
+
+    /// Marks the switch as deprecated in favour of another one (for example "--new-flag").
+    /// The switch is still honored, but Arguments::build records a warning pointing to the replacement.
+    pub fn deprecated_by(mut self, replacement: &str) -> Switch<'a> {
+        self.deprecated = Some(format!("deprecated, use {} instead", replacement));
+        self.replacement = Some(replacement.to_string());
+        self
+    }
+
+    /// Puts the switch under the given header in usage output.
+    pub fn category(mut self, category: &str) -> Switch<'a> {
+        self.category = Some(category.to_string());
+        self
+    }
+
+    /// Overrides the handler's requires_value: with false the switch is a flag and the handler's
+    /// set_value is called, with true the switch always takes a value.
+    pub fn takes_value(mut self, takes_value: bool) -> Switch<'a> {
+        self.takes_value = Some(takes_value);
+        self
+    }
+
+    /// Normalizes values before they are passed to the handler, e.g. |v| v.to_lowercase().
+    pub fn transform(mut self, transform: fn(&str) -> String) -> Switch<'a> {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Environment variable used by Arguments::apply_env when the switch is not given on the command line.
+    /// For flags, "1" and "true" set the flag.
+    pub fn env(mut self, name: &str) -> Switch<'a> {
+        self.env = Some(name.to_string());
+        self
+    }
+
+    /// Makes Arguments::build stop parsing when the switch is seen and collect all remaining arguments
+    /// verbatim for it, e.g. "--exec prog -a -b", see Arguments::captured.
+    pub fn captures_rest(mut self) -> Switch<'a> {
+        self.captures_rest = true;
+        self
+    }
+
+    /// Makes Arguments::build fail when the switch is not used.
+    pub fn required(mut self) -> Switch<'a> {
+        self.required = true;
+        self
+    }
+
+    /// Leaves the switch out of the usage text (e.g. for debug switches), it is still parsed.
+    pub fn hidden(mut self) -> Switch<'a> {
+        self.hidden = true;
+        self
+    }
+
+    /// Longer description shown by Arguments::usage_for.
+    pub fn description(mut self, description: &str) -> Switch<'a> {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Usage example shown by Arguments::usage_for, e.g. "-p 8080".
+    pub fn example(mut self, example: &str) -> Switch<'a> {
+        self.example = Some(example.to_string());
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_short(&self) -> Option<char> {
+        self.switch
+    }
+
+    pub fn get_long(&self) -> Option<&str> {
+        self.ext_switch.as_deref()
+    }
+
+    fn usage_details(&self) -> String {
+        let mut result = self.name.clone();
+        result.push('\n');
+        let mut switches = Vec::new();
+        if let Some(sw) = self.switch {
+            switches.push(format!("-{}", sw));
+        }
+        if let Some(sw) = &self.ext_switch {
+            switches.push(format!("--{}", sw));
+        }
+        result.push_str(format!("  switches: {}\n", switches.join(", ")).as_str());
+        if self.requires_value() {
+            result.push_str(format!("  type:{}\n", self.handler.value_type()).as_str());
+            result.push_str(format!("  default: {}\n", self.handler.value_string()).as_str());
+        }
+        if let Some(example) = &self.example {
+            result.push_str(format!("  example: {}\n", example).as_str());
+        }
+        if let Some(description) = &self.description {
+            result.push_str(format!("  {}\n", description).as_str());
+        }
+        result
+    }
+
+    fn usage_line(&self, colored: bool) -> String {
+        let mut result = "".to_string();
+        if let Some(sw) = self.switch {
+            result.push_str(format!(" {}", paint(&format!("-{}", sw), GREEN, colored)).as_str());
+            if let Some(sw) = &self.ext_switch {
+                result.push_str(format!(" (or {})", paint(&format!("--{}", sw), GREEN, colored)).as_str());
+            }
+        } else if let Some(sw) = &self.ext_switch {
+            result.push_str(format!(" {}", paint(&format!("--{}", sw), GREEN, colored)).as_str());
+        }
+        if self.requires_value() {
+            let value_type = self.handler.value_type();
+            let type_name = value_type.trim_start();
+            result.push_str(&value_type[..value_type.len() - type_name.len()]);
+            result.push_str(paint(type_name, YELLOW, colored).as_str());
+        }
+        result.push_str(" - ");
+        result.push_str(self.name.as_str());
+        result
+    }
+
+    fn parse_value(&self, value: &str) -> bool {
+        match self.transform {
+            Some(transform) => self.handler.parse_value(&transform(value)),
+            None => self.handler.parse_value(value)
+        }
+    }
+
+    fn requires_value(&self) -> bool {
+        self.takes_value.unwrap_or_else(|| self.handler.requires_value())
+    }
+
+    fn value_count(&self) -> usize {
+        self.handler.value_count()
+    }
+
+    fn parse_values(&self, values: &[&str]) -> bool {
+        match self.transform {
+            Some(transform) => {
+                let values: Vec<String> = values.iter().map(|v| transform(v)).collect();
+                self.handler.parse_values(&values.iter().map(|v| v.as_str()).collect::<Vec<&str>>())
+            }
+            None => self.handler.parse_values(values)
+        }
+    }
+
+    fn set_value(&self) {
+        self.handler.set_value()
+    }
+}
+
+/// Short summary of the switches for the usage line, e.g. " [-vq] [-p PORT] --name NAME":
+/// optional short flags are grouped, value-taking switches get the upper-cased switch name as a placeholder,
+/// required switches are not in brackets.
+fn synopsis(switches: &[&Switch]) -> String {
+    let flags: String = switches.iter()
+        .filter(|sw| !sw.required && !sw.requires_value())
+        .filter_map(|sw| sw.switch)
+        .collect();
+    let mut result = if flags.is_empty() { "".to_string() } else { format!(" [-{}]", flags) };
+    for sw in switches {
+        let item = match (sw.switch, &sw.ext_switch) {
+            (Some(_), _) if !sw.required && !sw.requires_value() => continue,
+            (Some(short), _) => format!("-{}", short),
+            (None, Some(long)) => format!("--{}", long),
+            (None, None) => continue
+        };
+        let item = if sw.requires_value() { format!("{} {}", item, sw.name.to_uppercase()) } else { item };
+        if sw.required {
+            result.push_str(format!(" {}", item).as_str());
+        } else {
+            result.push_str(format!(" [{}]", item).as_str());
+        }
+    }
+    result
+}
+
+/// Error and usage messages used by Arguments, English by default.
+/// In messages with {} it is replaced with the switch name.
+/// Messages returned by the handlers themselves (ValueHandler::validate and ValueHandler::error_message,
+/// e.g. CountParameter's "switch can be used at most 2 times") are out of scope and used as is.
+#[derive(Clone, Debug)]
+pub struct Messages {
+    pub usage: String,
+    pub options: String,
+    pub unknown_switch: String,
+    pub unknown_ext_switch: String,
+    pub invalid_switch: String,
+    pub invalid_value: String,
+    pub value_out_of_range: String,
+    pub not_a_number: String,
+    pub value_expected: String,
+    pub unexpected_value: String,
+    pub incorrect_number_of_arguments: String,
+    pub command_expected: String,
+    pub unknown_command: String,
+    pub unterminated_quote: String,
+    pub no_glob_match: String,
+    pub required_switch_missing: String,
+    pub exactly_one_required: String,
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Messages {
+            usage: "Usage: ".to_string(),
+            options: "Options".to_string(),
+            unknown_switch: "unknown switch".to_string(),
+            unknown_ext_switch: "unknown ext switch".to_string(),
+            invalid_switch: "invalid switch".to_string(),
+            invalid_value: "invalid {} value".to_string(),
+            value_out_of_range: "{} value out of range".to_string(),
+            not_a_number: "{} value is not a number".to_string(),
+            value_expected: "value expected for switch {}".to_string(),
+            unexpected_value: "switch {} does not take a value".to_string(),
+            incorrect_number_of_arguments: "incorrect number of arguments".to_string(),
+            command_expected: "command expected".to_string(),
+            unknown_command: "unknown command {}".to_string(),
+            unterminated_quote: "unterminated quote".to_string(),
+            no_glob_match: "no files match {}".to_string(),
+            required_switch_missing: "switch {} is required".to_string(),
+            exactly_one_required: "exactly one of {} is required".to_string(),
+        }
+    }
+}
+
+fn message(template: &str, name: &str) -> String {
+    template.replace("{}", name)
+}
+
+fn split_line(line: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => arg.push(c)
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            c @ ('"' | '\\') => arg.push(c),
+                            c => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                        },
+                        c => arg.push(c)
+                    }
+                }
+            }
+            '\\' => current.get_or_insert_with(String::new).push(chars.next()?),
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            c => current.get_or_insert_with(String::new).push(c)
+        }
+    }
+    if let Some(arg) = current {
+        args.push(arg);
+    }
+    Some(args)
+}
+
+/// Definition of a switch in ParserSpec.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SwitchSpec {
+    pub name: String,
+    pub short: Option<char>,
+    pub long: Option<String>,
+    /// Value type as shown in usage (e.g. "int"), empty for flags.
+    pub value_type: String,
+    pub required: bool,
+    /// Current value of the handler, which is the default before build.
+    pub default: String,
+    pub description: Option<String>,
+    /// Usage header the switch is listed under, None for the default "Options".
+    pub category: Option<String>,
+    /// Left out of the usage text, generators should not document it either.
+    pub hidden: bool,
+}
+
+/// Machine-readable definition of the whole parser returned by Arguments::describe,
+/// e.g. for man page generators.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ParserSpec {
+    pub program_name: String,
+    pub positionals: Vec<String>,
+    pub switches: Vec<SwitchSpec>,
+}
+
+/// Reported by Arguments::build_with_callback while parsing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseEvent {
+    /// A switch (by name) was found on the command line.
+    SwitchSeen(String),
+    /// The value of a switch was parsed, values of a multi-value switch are joined with spaces.
+    ValueParsed { switch: String, value: String },
+    PositionalSeen(String),
+    /// Parsing failed, this is the last event.
+    Error(ParseError),
+}
+
+/// Help requested with the built-in --help switch, see Arguments::enable_help.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HelpRequest {
+    /// Bare --help: print the full usage.
+    Full,
+    /// --help=name: print usage_for(name).
+    Switch(String),
+}
+
+/// Check that runs after Arguments::build with access to switch presence and positional arguments.
+pub type ArgumentsValidator<'a> = Box<dyn Fn(&Arguments<'a>) -> Result<(), String> + 'a>;
+
+pub struct Arguments<'a> {
+    program_name: String,
+    switches: Vec<Switch<'a>>,
+    switch_map: HashMap<char, usize>,
+    ext_switch_map: HashMap<String, usize>,
+    other_arguments: Vec<String>,
+    trailing: Option<Vec<String>>,
+    other_argument_names: Option<Vec<String>>,
+    warnings: Vec<Warning>,
+    cross_validators: Vec<Box<dyn Fn() -> Result<(), String> + 'a>>,
+    validators: Vec<ArgumentsValidator<'a>>,
+    present: HashSet<usize>,
+    sources: HashMap<usize, ValueSource>,
+    raw_values: HashMap<String, String>,
+    captured: HashMap<String, Vec<String>>,
+    messages: Messages,
+    commands: Option<Vec<String>>,
+    command: Option<String>,
+    strict_values: bool,
+    bare_switches: bool,
+    help_enabled: bool,
+    help: Option<HelpRequest>,
+    #[cfg(feature = "glob")]
+    glob_expansion: Option<bool>,
+    one_of_groups: Vec<Vec<String>>,
+    usage_header: Option<String>,
+    defer_positional_validation: bool,
+}
+
+impl<'a> Arguments<'a> {
+    pub fn new(program_name: &str, switches: &[Switch<'a>], other_argument_names: Option<Vec<String>>) -> Arguments<'a> {
+        let mut arguments = Arguments {
+            program_name: program_name.to_string(),
+            switches: Vec::new(),
+            switch_map: HashMap::new(),
+            ext_switch_map: HashMap::new(),
+            other_arguments: Vec::new(),
+            trailing: None,
+            other_argument_names,
+            warnings: Vec::new(),
+            cross_validators: Vec::new(),
+            validators: Vec::new(),
+            present: HashSet::new(),
+            sources: HashMap::new(),
+            raw_values: HashMap::new(),
+            captured: HashMap::new(),
+            messages: Messages::default(),
+            commands: None,
+            command: None,
+            strict_values: false,
+            bare_switches: false,
+            help_enabled: false,
+            help: None,
+            #[cfg(feature = "glob")]
+            glob_expansion: None,
+            one_of_groups: Vec::new(),
+            usage_header: None,
+            defer_positional_validation: false,
+        };
+        for switch in switches {
+            arguments.add_switch(switch.clone());
+        }
+        arguments
+    }
+
+    /// Same as new, but returns an error if two switches share a short or long switch or a name
+    /// (e.g. "port" registered once for -p and once more for --port) or a switch has neither a short
+    /// nor a long switch.
+    pub fn try_new(program_name: &str, switches: &[Switch<'a>], other_argument_names: Option<Vec<String>>)
+        -> Result<Arguments<'a>, Error> {
+        let mut arguments = Arguments::new(program_name, &[], other_argument_names);
+        arguments.extend(switches)?;
+        Ok(arguments)
+    }
+
+    /// Registers additional switches (for example, contributed by a plugin).
+    /// Returns an error and registers nothing if any short or long switch or switch name is already in use
+    /// or a switch has neither a short nor a long switch, so it could never be matched.
+    pub fn extend(&mut self, other_switches: &[Switch<'a>]) -> Result<(), Error> {
+        let mut switches = HashSet::new();
+        let mut ext_switches = HashSet::new();
+        let mut names = HashSet::new();
+        for switch in other_switches {
+            if switch.switch.is_none() && switch.ext_switch.is_none() {
+                return Err(Error::new(ErrorKind::InvalidInput,
+                                      format!("switch {} has neither a short nor a long switch", switch.name)));
+            }
+            if self.switches.iter().any(|sw| sw.name == switch.name) || !names.insert(&switch.name) {
+                return Err(Error::new(ErrorKind::AlreadyExists, format!("duplicate switch name {}", switch.name)));
+            }
+            if let Some(sw) = switch.switch {
+                if self.switch_map.contains_key(&sw) || !switches.insert(sw) {
+                    return Err(Error::new(ErrorKind::AlreadyExists, format!("duplicate switch -{}", sw)));
+                }
+            }
+            if let Some(sw) = &switch.ext_switch {
+                if self.ext_switch_map.contains_key(sw) || !ext_switches.insert(sw) {
+                    return Err(Error::new(ErrorKind::AlreadyExists, format!("duplicate ext switch --{}", sw)));
+                }
+            }
+        }
+        for switch in other_switches {
+            self.add_switch(switch.clone());
+        }
+        Ok(())
+    }
+
+    fn add_switch(&mut self, switch: Switch<'a>) {
+        let idx = self.switches.len();
+        if let Some(sw) = switch.switch {
+            self.switch_map.insert(sw, idx);
+        }
+        if let Some(sw) = &switch.ext_switch {
+            self.ext_switch_map.insert(sw.clone(), idx);
+        }
+        self.switches.push(switch);
+    }
+
+    /// Replaces the built-in (English) error and usage messages, e.g. with translated ones.
+    pub fn set_messages(&mut self, messages: Messages) {
+        self.messages = messages;
+    }
+
+    /// Requires the first positional argument to be one of the given command names.
+    /// The command is removed from the positional arguments and is available via command().
+    pub fn set_commands(&mut self, commands: Vec<String>) {
+        self.commands = Some(commands);
+    }
+
+    /// In strict mode a value-taking switch does not accept a registered switch (e.g. "-v") as its value,
+    /// so "--message -v" fails with a value expected error instead of using "-v" as the message.
+    pub fn set_strict_values(&mut self, strict_values: bool) {
+        self.strict_values = strict_values;
+    }
+
+    /// Skips the check that the number of positional arguments matches the argument names, e.g. to start
+    /// a REPL without arguments and a batch run with them. Check positional_count after build instead.
+    pub fn set_defer_positional_validation(&mut self, defer: bool) {
+        self.defer_positional_validation = defer;
+    }
+
+    /// Compatibility mode for legacy command lines: a token that exactly matches a registered long switch
+    /// without the dashes (e.g. "verbose" for --verbose) is treated as that switch, not as a positional argument.
+    pub fn set_bare_switches(&mut self, bare_switches: bool) {
+        self.bare_switches = bare_switches;
+    }
+
+    /// Replaces the first line of the usage text ("Usage: cache [-v] arg1"), e.g. "USAGE:\n  {program} {positionals}".
+    /// {program} is replaced with the program name, {switches} with the switch synopsis and
+    /// {positionals} with the positional argument names.
+    pub fn set_usage_header(&mut self, header: &str) {
+        self.usage_header = Some(header.to_string());
+    }
+
+    /// Collects all arguments after "--" verbatim into get_trailing instead of the positional arguments,
+    /// e.g. for a wrapper like "run -p 80 -- ls -la /tmp".
+    pub fn set_collect_trailing(&mut self, collect_trailing: bool) {
+        self.trailing = if collect_trailing { Some(Vec::new()) } else { None };
+    }
+
+    /// Enables the built-in --help switch (unless a --help switch is registered). When it is used, build stops
+    /// parsing and returns Ok, and help_request tells whether the full usage or help for one switch
+    /// (--help=port) was requested.
+    pub fn enable_help(&mut self) {
+        self.help_enabled = true;
+    }
+
+    /// Help requested in the last build call, if any.
+    pub fn help_request(&self) -> Option<&HelpRequest> {
+        self.help.as_ref()
+    }
+
+    /// Expands positional arguments with '*' or '?' into the sorted list of matching paths, e.g. for
+    /// "cache *.log" from a shell that does not expand globs. A pattern that matches nothing fails build
+    /// when error_if_no_match is set and is kept as is otherwise.
+    #[cfg(feature = "glob")]
+    pub fn set_glob_expansion(&mut self, enabled: bool, error_if_no_match: bool) {
+        self.glob_expansion = if enabled { Some(error_if_no_match) } else { None };
+    }
+
+    /// Command given in the last build call, if commands were set.
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    /// Prints the usage to stdout, in color when stdout is a terminal and NO_COLOR is not set.
+    pub fn usage(&self) {
+        let stdout = io::stdout();
+        if stdout.is_terminal() && std::env::var_os("NO_COLOR").is_none() {
+            let _ = writeln!(stdout.lock(), "{}", self.usage_colored_string());
+        } else {
+            let _ = self.write_usage(&mut stdout.lock());
+        }
+    }
+
+    /// Detailed help for a single switch, found by its name or long switch.
+    /// The default shown is the current value, so call it before build to get the real default.
+    pub fn usage_for(&self, switch_name: &str) -> Option<String> {
+        self.switch(switch_name).map(|sw| sw.usage_details())
+    }
+
+    /// Finds a switch by "-p", "--port", its name or its long switch without dashes.
+    pub fn switch(&self, key: &str) -> Option<&Switch<'a>> {
+        let idx = if let Some(ext_switch) = key.strip_prefix("--") {
+            self.ext_switch_map.get(ext_switch).copied()
+        } else if let Some(switch) = key.strip_prefix('-') {
+            let mut chars = switch.chars();
+            match (chars.next(), chars.next()) {
+                (Some(sw), None) => self.switch_map.get(&sw).copied(),
+                _ => None
+            }
+        } else {
+            self.switches.iter().position(|sw| sw.name == key)
+                .or_else(|| self.ext_switch_map.get(key).copied())
+        };
+        idx.map(|idx| &self.switches[idx])
+    }
+
+    /// Describes the program name, the positional argument names and all switches, including hidden ones.
+    pub fn describe(&self) -> ParserSpec {
+        ParserSpec {
+            program_name: self.program_name.clone(),
+            positionals: self.other_argument_names.clone().unwrap_or_default(),
+            switches: self.switches.iter().map(|sw| SwitchSpec {
+                name: sw.name.clone(),
+                short: sw.switch,
+                long: sw.ext_switch.clone(),
+                value_type: if sw.requires_value() {
+                    sw.handler.value_type().trim().to_string()
+                } else {
+                    String::new()
+                },
+                required: sw.required,
+                default: sw.handler.value_string(),
+                description: sw.description.clone(),
+                category: sw.category.clone(),
+                hidden: sw.hidden,
+            }).collect(),
+        }
+    }
+
+    /// Writes the usage text to any writer, e.g. stderr or a buffer.
+    pub fn write_usage<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "{}", self.usage_string())
+    }
+
+    /// Writes the error message followed by the usage text, the way parse_or_exit reports errors.
+    pub fn write_error<W: Write>(&self, w: &mut W, error: &Error) -> io::Result<()> {
+        writeln!(w, "{}", error)?;
+        self.write_usage(w)
+    }
+
+    /// Calls build, on error prints the error and the usage text to stderr and exits with code 2.
+    pub fn parse_or_exit(&mut self, args: &[String]) {
+        if let Err(e) = self.build(args.to_vec()) {
+            let _ = self.write_error(&mut io::stderr().lock(), &e);
+            std::process::exit(2);
+        }
+    }
+
+    /// Builds the usage text. When some switches have a category, switches are printed under their
+    /// category headers (uncategorized ones under "Options"), sorted by name within each group.
+    pub fn usage_string(&self) -> String {
+        self.build_usage(false)
+    }
+
+    /// Same as usage_string, but with switches in green and value types in yellow (ANSI escape codes).
+    pub fn usage_colored_string(&self) -> String {
+        self.build_usage(true)
+    }
+
+    fn build_usage(&self, colored: bool) -> String {
+        let switches: Vec<&Switch> = self.switches.iter().filter(|sw| !sw.hidden).collect();
+        let positionals = self.other_argument_names.as_ref().map(|names| names.join(" ")).unwrap_or_default();
+        let mut usage = match &self.usage_header {
+            Some(header) => header.replace("{program}", &self.program_name)
+                .replace("{switches}", synopsis(&switches).trim_start())
+                .replace("{positionals}", &positionals),
+            None => {
+                let mut usage = self.messages.usage.clone() + &self.program_name + &synopsis(&switches);
+                if !positionals.is_empty() {
+                    usage.push(' ');
+                    usage.push_str(&positionals);
+                }
+                usage
+            }
+        };
+        usage.push('\n');
+        if switches.iter().all(|sw| sw.category.is_none()) {
+            for sw in &switches {
+                usage.push_str(sw.usage_line(colored).as_str());
+                usage.push('\n');
+            }
+            return usage;
+        }
+        let mut categories: Vec<Option<&String>> = vec![None];
+        for sw in &switches {
+            if let Some(category) = &sw.category {
+                if !categories.contains(&Some(category)) {
+                    categories.push(Some(category));
+                }
+            }
+        }
+        for category in categories {
+            let mut group: Vec<&Switch> = switches.iter().copied()
+                .filter(|sw| sw.category.as_ref() == category)
+                .collect();
+            if group.is_empty() {
+                continue;
+            }
+            group.sort_by(|a, b| a.name.cmp(&b.name));
+            usage.push_str(category.unwrap_or(&self.messages.options));
+            usage.push_str(":\n");
+            for sw in group {
+                usage.push_str(sw.usage_line(colored).as_str());
+                usage.push('\n');
+            }
+        }
+        usage
+    }
+
+    /// Parses the arguments. Can be called again on the same instance: positional arguments, presence,
+    /// warnings and the command of the previous call are reset, parameters keep their values.
+    pub fn build(&mut self, args: Vec<String>) -> Result<(), Error> {
+        self.parse(args, false, &mut |_| {}).map(|_| ())
+    }
+
+    /// Same as build, but args includes the program name as its first element (argv[0]), which is ignored,
+    /// e.g. build_full(std::env::args().collect()). build expects the arguments without it.
+    pub fn build_full(&mut self, args: Vec<String>) -> Result<(), Error> {
+        self.build(args.into_iter().skip(1).collect())
+    }
+
+    /// Same as build, but stops at the first unknown switch instead of failing, e.g. before a subcommand
+    /// that has its own parser. Returns the number of arguments consumed, so args[consumed..] is the rest.
+    pub fn build_until_unknown(&mut self, args: Vec<String>) -> Result<usize, Error> {
+        self.parse(args, true, &mut |_| {})
+    }
+
+    /// Same as build, but reports parse events to the callback as arguments are consumed, e.g. for tracing.
+    /// A failed build ends with a ParseEvent::Error.
+    pub fn build_with_callback(&mut self, args: &[String], mut callback: impl FnMut(ParseEvent)) -> Result<(), Error> {
+        let result = self.parse(args.to_vec(), false, &mut callback).map(|_| ());
+        if let Some(error) = result.as_ref().err().and_then(ParseError::from_error) {
+            callback(ParseEvent::Error(error.clone()));
+        }
+        result
+    }
+
+    fn parse(&mut self, args: Vec<String>, stop_at_unknown: bool, events: &mut dyn FnMut(ParseEvent))
+        -> Result<usize, Error> {
+        self.warnings.clear();
+        self.other_arguments.clear();
+        self.present.clear();
+        self.sources.clear();
+        self.raw_values.clear();
+        self.captured.clear();
+        self.command = None;
+        self.help = None;
+        if let Some(trailing) = &mut self.trailing {
+            trailing.clear();
+        }
+        for sw in &self.switches {
+            sw.handler.reset();
+        }
+        let mut current_parameter: Option<(usize, String)> = None;
+        let mut values = Vec::new();
+        let mut options_ended = false;
+        let mut capturing: Option<String> = None;
+        let mut consumed = args.len();
+        for (i, arg) in args.into_iter().enumerate() {
+            if let Some(name) = &capturing {
+                self.captured.entry(name.clone()).or_default().push(arg);
+            } else if let Some((idx, switch)) = &current_parameter {
+                if self.strict_values && self.is_switch(&arg) {
+                    return Err(ParseError::new(ParseErrorKind::ValueExpected, Some(switch),
+                                               &message(&self.messages.value_expected, switch)).into());
+                }
+                let idx = *idx;
+                values.push(arg);
+                if values.len() < self.switches[idx].value_count() {
+                    continue;
+                }
+                self.parse_switch_values(idx, &values, events)?;
+                values.clear();
+                current_parameter = None;
+            } else if options_ended {
+                match &mut self.trailing {
+                    Some(trailing) => trailing.push(arg),
+                    None => {
+                        events(ParseEvent::PositionalSeen(arg.clone()));
+                        self.other_arguments.push(arg);
+                    }
+                }
+            } else if arg == "--" {
+                // end of options, all remaining arguments (including further "--") are positional
+                options_ended = true;
+            } else if arg.starts_with('-') || (self.bare_switches && self.ext_switch_map.contains_key(&arg)) {
+                let mut attached_value = None;
+                let idx = if let Some(ext_switch) = arg.strip_prefix("--") {
+                    // --name=value, the value may be empty and may contain further '='
+                    let ext_switch = match ext_switch.split_once('=') {
+                        Some((name, value)) => {
+                            attached_value = Some(value.to_string());
+                            name
+                        }
+                        None => ext_switch
+                    };
+                    if let Some(idx) = self.ext_switch_map.get(ext_switch) {
+                        *idx
+                    } else if self.help_enabled && ext_switch == "help" {
+                        self.help = Some(attached_value.map(HelpRequest::Switch).unwrap_or(HelpRequest::Full));
+                        return Ok(i + 1);
+                    } else if stop_at_unknown {
+                        consumed = i;
+                        break;
+                    } else {
+                        return Err(ParseError::new(ParseErrorKind::UnknownSwitch, Some(&arg),
+                                                   &self.messages.unknown_ext_switch).into());
+                    }
+                } else if !arg.starts_with('-') {
+                    self.ext_switch_map[&arg]
+                } else {
+                    let flags: Vec<char> = arg.chars().skip(1).collect();
+                    if flags.is_empty() {
+                        return Err(ParseError::new(ParseErrorKind::InvalidSwitch, Some(&arg),
+                                                   &self.messages.invalid_switch).into());
+                    }
+                    if stop_at_unknown && self.has_unknown_flag(&flags) {
+                        consumed = i;
+                        break;
+                    }
+                    match self.set_flags(&arg, &flags, events)? {
+                        Some((idx, value)) => {
+                            attached_value = value;
+                            idx
+                        }
+                        None => continue
+                    }
+                };
+                self.switch_used(idx, events);
+                let p = &self.switches[idx];
+                if p.captures_rest {
+                    p.set_value();
+                    self.captured.entry(p.name.clone()).or_default().extend(attached_value);
+                    capturing = Some(p.name.clone());
+                } else if p.requires_value() {
+                    if let Some(value) = attached_value {
+                        values.push(value);
+                        if values.len() < p.value_count() {
+                            current_parameter = Some((idx, arg));
+                        } else {
+                            self.parse_switch_values(idx, &values, events)?;
+                            values.clear();
+                        }
+                    } else {
+                        current_parameter = Some((idx, arg));
+                    }
+                } else if attached_value.is_some() {
+                    return Err(ParseError::new(ParseErrorKind::UnexpectedValue, Some(&p.name),
+                                               &message(&self.messages.unexpected_value, &p.name)).into());
+                } else {
+                    p.set_value();
+                }
+            } else if let Some(escaped) = arg.strip_prefix("\\-") {
+                // "\-name" is a positional argument that starts with '-'
+                let arg = format!("-{}", escaped);
+                events(ParseEvent::PositionalSeen(arg.clone()));
+                self.other_arguments.push(arg);
+            } else {
+                #[cfg(feature = "glob")]
+                if self.glob_expansion.is_some() && arg.contains(['*', '?']) {
+                    for path in self.expand_glob(&arg)? {
+                        events(ParseEvent::PositionalSeen(path.clone()));
+                        self.other_arguments.push(path);
+                    }
+                    continue;
+                }
+                events(ParseEvent::PositionalSeen(arg.clone()));
+                self.other_arguments.push(arg);
+            }
+        }
+        if let Some((_, switch)) = current_parameter {
+            return Err(ParseError::new(ParseErrorKind::ValueExpected, Some(&switch),
+                                       &message(&self.messages.value_expected, &switch)).into());
+        }
+        if let Some(commands) = &self.commands {
+            if self.other_arguments.is_empty() {
+                return Err(ParseError::new(ParseErrorKind::CommandExpected, None,
+                                           &self.messages.command_expected).into());
+            }
+            let command = self.other_arguments.remove(0);
+            if !commands.contains(&command) {
+                return Err(ParseError::new(ParseErrorKind::UnknownCommand, Some(&command),
+                                           &message(&self.messages.unknown_command, &command)).into());
+            }
+            self.command = Some(command);
+        }
+        if let Some(other_argument_names) = self.other_argument_names.as_ref() {
+            if !self.defer_positional_validation && other_argument_names.len() != self.other_arguments.len() {
+                return Err(ParseError::new(ParseErrorKind::IncorrectArgumentCount, None,
+                                           &self.messages.incorrect_number_of_arguments).into());
+            }
+        }
+        if let Some(name) = self.required_missing().first() {
+            return Err(ParseError::new(ParseErrorKind::RequiredSwitchMissing, Some(name),
+                                       &message(&self.messages.required_switch_missing, name)).into());
+        }
+        for group in &self.one_of_groups {
+            let used = self.present.iter()
+                .filter(|idx| {
+                    let sw = &self.switches[**idx];
+                    group.iter().any(|name| sw.name == *name || sw.ext_switch.as_ref() == Some(name))
+                })
+                .count();
+            if used != 1 {
+                let names = group.join(", ");
+                return Err(ParseError::new(ParseErrorKind::ValidationFailed, None,
+                                           &message(&self.messages.exactly_one_required, &names)).into());
+            }
+        }
+        for sw in &self.switches {
+            sw.handler.validate().map_err(|e| ParseError::new(ParseErrorKind::ValidationFailed, Some(&sw.name), &e))?;
+        }
+        for validator in &self.cross_validators {
+            validator().map_err(|e| ParseError::new(ParseErrorKind::ValidationFailed, None, &e))?;
+        }
+        for validator in &self.validators {
+            validator(self).map_err(|e| ParseError::new(ParseErrorKind::ValidationFailed, None, &e))?;
+        }
+        Ok(consumed)
+    }
+
+    /// Requires exactly one switch of the group (given by names or long switches) to be used,
+    /// e.g. require_one_of(&["input-file", "stdin"]).
+    pub fn require_one_of(&mut self, names: &[&str]) {
+        self.one_of_groups.push(names.iter().map(|name| name.to_string()).collect());
+    }
+
+    /// Adds a check that runs after all arguments are parsed and gets the whole parser,
+    /// so it can look at was_present and get_other_arguments.
+    pub fn add_validator(&mut self, validator: ArgumentsValidator<'a>) {
+        self.validators.push(validator);
+    }
+
+    /// Returns true if a switch with the given name was used in the last build call.
+    pub fn was_present(&self, name: &str) -> bool {
+        self.present.iter().any(|idx| self.switches[*idx].name == name)
+    }
+
+    /// Values of the switches used in the last build call as given on the command line, by switch name.
+    /// Flags have "true", values of a multi-value switch are joined with spaces.
+    pub fn raw_values(&self) -> &HashMap<String, String> {
+        &self.raw_values
+    }
+
+    /// Where the value of the switch with the given name came from in the last build call.
+    pub fn value_source(&self, name: &str) -> ValueSource {
+        self.switches.iter().position(|sw| sw.name == name)
+            .and_then(|idx| self.sources.get(&idx).copied())
+            .unwrap_or(ValueSource::Default)
+    }
+
+    /// Names of the required switches that were not used in the last build call. It is filled even when
+    /// build fails, e.g. to highlight the missing fields in a UI.
+    pub fn required_missing(&self) -> Vec<String> {
+        self.switches.iter().enumerate()
+            .filter(|(idx, sw)| sw.required && !self.present.contains(idx))
+            .map(|(_, sw)| sw.name.clone())
+            .collect()
+    }
+
+    /// Sets the switches that were not used in the last build call from their environment variables
+    /// (see Switch::env) and records ValueSource::Env for them. The command line takes precedence over
+    /// the environment, the environment over the defaults. Usually called after build; when called before,
+    /// it skips the switches used in the previous build call, and build then resets the value sources.
+    pub fn apply_env(&mut self) -> Result<(), ParseError> {
+        for (idx, p) in self.switches.iter().enumerate() {
+            if self.present.contains(&idx) {
+                continue;
+            }
+            let Some(value) = p.env.as_ref().and_then(|name| std::env::var(name).ok()) else {
+                continue;
+            };
+            if !p.requires_value() {
+                if value != "1" && value != "true" {
+                    continue;
+                }
+                p.set_value();
+            } else if !p.parse_value(&value) {
+                return Err(self.value_error(p, Some(&value)));
+            }
+            self.sources.insert(idx, ValueSource::Env);
+        }
+        Ok(())
+    }
+
+    /// Number of distinct switches used in the last build call, e.g. 0 to fall back to interactive mode.
+    pub fn parsed_switch_count(&self) -> usize {
+        self.present.len()
+    }
+
+    /// Returns true if the short switch was used in the last build call, e.g. flag('v') for -v.
+    pub fn flag(&self, switch: char) -> bool {
+        self.switch_map.get(&switch).is_some_and(|idx| self.present.contains(idx))
+    }
+
+    /// Adds a check that runs after all arguments are parsed, e.g. to verify that --max >= --min.
+    /// The closure usually captures references to the parameters it compares.
+    pub fn add_cross_validator(&mut self, validator: impl Fn() -> Result<(), String> + 'a) {
+        self.cross_validators.push(Box::new(validator));
+    }
+
+    /// Same as build, but takes the whole command line as a single string and splits it like a shell:
+    /// single quotes keep everything literally, in double quotes a backslash escapes '"' and '\\',
+    /// outside of quotes a backslash escapes any character.
+    pub fn build_from_line(&mut self, line: &str) -> Result<(), Error> {
+        let args = split_line(line).ok_or_else(||
+            ParseError::new(ParseErrorKind::UnterminatedQuote, None, &self.messages.unterminated_quote))?;
+        self.build(args)
+    }
+
+    fn parse_switch_values(&mut self, idx: usize, values: &[String], events: &mut dyn FnMut(ParseEvent))
+        -> Result<(), Error> {
+        let p = &self.switches[idx];
+        let parsed = if values.len() == 1 {
+            p.parse_value(values[0].as_str())
+        } else {
+            p.parse_values(&values.iter().map(|v| v.as_str()).collect::<Vec<&str>>())
+        };
+        if !parsed {
+            let value = if values.len() == 1 { Some(values[0].as_str()) } else { None };
+            return Err(self.value_error(p, value).into());
+        }
+        let name = p.name.clone();
+        events(ParseEvent::ValueParsed { switch: name.clone(), value: values.join(" ") });
+        self.raw_values.insert(name, values.join(" "));
+        Ok(())
+    }
+
+    /// Error for a rejected value, value is None when several values were rejected together.
+    fn value_error(&self, p: &Switch, value: Option<&str>) -> ParseError {
+        let kind = value.map(|v| p.handler.error_kind(v)).unwrap_or(ParseErrorKind::InvalidValue);
+        let message = match (value.and_then(|v| p.handler.error_message(v)), kind) {
+            (Some(message), _) => message,
+            (None, ParseErrorKind::ValueOutOfRange) => message(&self.messages.value_out_of_range, &p.name),
+            (None, ParseErrorKind::NotANumber) => message(&self.messages.not_a_number, &p.name),
+            (None, _) => message(&self.messages.invalid_value, &p.name)
+        };
+        ParseError::new(kind, Some(&p.name), &message)
+    }
+
+    /// Handles a short switch or a cluster of them, e.g. -v, -vvv, -p3333 or -vxp3333:
+    /// flags are set from left to right until the first switch that takes a value, the rest of the token
+    /// is that switch's value (when the rest is empty, the value is the next argument). A switch marked
+    /// with Switch::captures_rest ends the cluster the same way, the rest of the token is its first captured argument.
+    /// Characters after the value-taking switch are never read as flags: with -p taking a value,
+    /// -pvx3333 gives -p the value "vx3333" and leaves -v and -x unset.
+    /// Returns the value-taking switch with its attached value, if there is one.
+    fn set_flags(&mut self, arg: &str, flags: &[char], events: &mut dyn FnMut(ParseEvent))
+        -> Result<Option<(usize, Option<String>)>, Error> {
+        for (i, flag) in flags.iter().enumerate() {
+            let idx = match self.switch_map.get(flag) {
+                Some(idx) => *idx,
+                None => return Err(ParseError::new(ParseErrorKind::UnknownSwitch, Some(arg),
+                                                   &self.messages.unknown_switch).into())
+            };
+            if self.switches[idx].requires_value() || self.switches[idx].captures_rest {
+                let value: String = flags[i + 1..].iter().collect();
+                return Ok(Some((idx, if value.is_empty() { None } else { Some(value) })));
+            }
+            self.switch_used(idx, events);
+            self.switches[idx].set_value();
+        }
+        Ok(None)
+    }
+
+    #[cfg(feature = "glob")]
+    fn expand_glob(&self, pattern: &str) -> Result<Vec<String>, Error> {
+        let paths = glob::glob(pattern).map_err(|_|
+            ParseError::new(ParseErrorKind::InvalidValue, Some(pattern),
+                            &message(&self.messages.invalid_value, pattern)))?;
+        let mut matches: Vec<String> = paths.filter_map(|p| p.ok()).map(|p| p.to_string_lossy().to_string()).collect();
+        if matches.is_empty() {
+            if self.glob_expansion == Some(true) {
+                return Err(ParseError::new(ParseErrorKind::NoGlobMatch, Some(pattern),
+                                           &message(&self.messages.no_glob_match, pattern)).into());
+            }
+            matches.push(pattern.to_string());
+        }
+        matches.sort();
+        Ok(matches)
+    }
+
+    /// Checks the flags of a short switch cluster the way set_flags walks them.
+    fn has_unknown_flag(&self, flags: &[char]) -> bool {
+        for flag in flags {
+            match self.switch_map.get(flag) {
+                Some(idx) if self.switches[*idx].requires_value() || self.switches[*idx].captures_rest => return false,
+                Some(_) => {}
+                None => return true
+            }
+        }
+        false
+    }
+
+    /// True for --name, --name=value, -c and a -cxyz cluster starting with a registered switch.
+    fn is_switch(&self, arg: &str) -> bool {
+        if let Some(ext_switch) = arg.strip_prefix("--") {
+            let name = ext_switch.split_once('=').map_or(ext_switch, |(name, _)| name);
+            return self.ext_switch_map.contains_key(name);
+        }
+        let mut chars = arg.chars();
+        match (chars.next(), chars.next()) {
+            (Some('-'), Some(sw)) => self.switch_map.contains_key(&sw),
+            _ => false
+        }
+    }
+
+    fn switch_used(&mut self, idx: usize, events: &mut dyn FnMut(ParseEvent)) {
+        events(ParseEvent::SwitchSeen(self.switches[idx].name.clone()));
+        self.present.insert(idx);
+        self.sources.insert(idx, ValueSource::Cli);
+        let p = &self.switches[idx];
+        if !p.requires_value() {
+            self.raw_values.insert(p.name.clone(), "true".to_string());
+        }
+        if let Some(message) = &p.deprecated {
+            let warning = match &p.replacement {
+                Some(replacement) => Warning::with_replacement(&p.name, message, replacement),
+                None => Warning::new(&p.name, message)
+            };
+            self.warnings.push(warning);
+        }
+    }
+
+    pub fn get_other_arguments(&self) -> &Vec<String> {
+        &self.other_arguments
+    }
+
+    /// Number of positional arguments of the last build call.
+    pub fn positional_count(&self) -> usize {
+        self.other_arguments.len()
+    }
+
+    /// Returns true if the last build call got any positional arguments.
+    pub fn has_positionals(&self) -> bool {
+        !self.other_arguments.is_empty()
+    }
+
+    /// Removes the positional arguments of the last build call (build also does it before parsing).
+    pub fn clear_other_arguments(&mut self) {
+        self.other_arguments.clear();
+    }
+
+    /// Arguments collected after a switch marked with Switch::captures_rest in the last build call,
+    /// empty if the switch was not used.
+    pub fn captured(&self, name: &str) -> &[String] {
+        self.captured.get(name).map(|v| v.as_slice()).unwrap_or_default()
+    }
+
+    /// Arguments after "--" when set_collect_trailing is on, empty otherwise.
+    pub fn get_trailing(&self) -> &[String] {
+        self.trailing.as_deref().unwrap_or_default()
+    }
+
+    /// Parses the positional argument at the given index, e.g. a count or a port number.
+    pub fn positional_as<T: FromStr>(&self, index: usize) -> Result<T, ParseError> {
+        let name = self.other_argument_names.as_ref()
+            .and_then(|names| names.get(index).cloned())
+            .unwrap_or_else(|| format!("argument {}", index + 1));
+        let value = self.other_arguments.get(index).ok_or_else(||
+            ParseError::new(ParseErrorKind::IncorrectArgumentCount, Some(&name),
+                            &self.messages.incorrect_number_of_arguments))?;
+        T::from_str(value).map_err(|_|
+            ParseError::new(ParseErrorKind::InvalidValue, Some(&name), &message(&self.messages.invalid_value, &name)))
+    }
+
+    /// Current value of the switch found by Arguments::switch, parsed from the handler's value_string,
+    /// e.g. get_value::<isize>("port"). None if there is no such switch or the value does not parse.
+    /// Some handlers have a value_string that does not parse back (SecretParameter gives "*****",
+    /// TemperatureParameter "100C"), use Arguments::handler for them.
+    pub fn get_value<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.switch(key).and_then(|sw| T::from_str(&sw.handler.value_string()).ok())
+    }
+
+    /// Handler of a switch created with Switch::new_typed, found by Arguments::switch.
+    /// None if there is no such switch, it was created otherwise or the handler has another type.
+    pub fn handler<H: 'static>(&self, key: &str) -> Option<&H> {
+        match &self.switch(key)?.handler {
+            SwitchHandler::Owned(_, Some(handler)) => handler.downcast_ref::<H>(),
+            _ => None
+        }
+    }
+
+    /// Warnings collected during the last build call.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use crate::validators;
+    use crate::{AliasSwitch, Arguments, BoolParameter, BoundedStringParameter, ChoiceOrFreeParameter,
+                ColonKeyValueParameter, CountParameter, DecimalScaledParameter, EmailParameter, EnumParameter,
+                ExpandingStringParameter, ExplicitBoolParameter, FileSetEnumParameter, FixedCsvParameter,
+                HelpRequest, IntParameter, IsoDurationParameter, LastWinsKeyValueParameter, MappedEnumParameter,
+                Messages, MultiEnumParameter, NaryParameter, NonEmptyStringParameter, OptionalParameter,
+                ParseError, ParseErrorKind, ParseEvent, PathListParameter, PointerParameter, PowerOfTwoParameter,
+                QuotedCsvParameter, RangeSetParameter, SecretParameter, SignedAdjustmentParameter,
+                SignedSizeParameter, SizeParameter, StdinOrValueStringParameter, StringParameter, Switch,
+                TemperatureParameter, ToggleGroup, ToggleMode, UrlParameter, ValueHandler, ValueSource};
+
+    #[test]
+    fn test_arguments_parser() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let max_memory_parameter = SizeParameter::new(1024 * 1024 * 1024, |v|v>0);//1G
+        let threads_parameter = IntParameter::new(4, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let string_parameter = StringParameter::new("init");
+        let enum_parameter = EnumParameter::new(vec!["value".to_string()], "");
+        let switches = [
+            Switch::new("port", Some('p'), None, &port_parameter),
+            Switch::new("maximum_memory", Some('m'), None, &max_memory_parameter),
+            Switch::new("threads", Some('t'), None, &threads_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+            Switch::new("test", None, Some("ss"), &string_parameter),
+            Switch::new("test_enum", Some('e'), None, &enum_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches,
+                                           Some(vec!["arg1".to_string(), "arg2".to_string()]));
+        let result = arguments.build(vec![
+            "-p".to_string(), "3333".to_string(),
+            "-m".to_string(), "1M".to_string(),
+            "-t".to_string(), "12".to_string(),
+            "-v".to_string(),
+            "--ss".to_string(), "test".to_string(),
+            "-e".to_string(), "value".to_string(),
+            "arg1".to_string(), "arg2".to_string()]);
+        assert!(result.is_ok(), "{}", result.err().map(|e|e.to_string()).unwrap_or("".to_string()));
+        assert_eq!(3333, port_parameter.get_value());
+        assert_eq!(1024 * 1024, max_memory_parameter.get_value());
+        assert_eq!(12, threads_parameter.get_value());
+        assert!(verbose_parameter.get_value());
+        assert_eq!("test", string_parameter.get_value());
+        assert_eq!("value", enum_parameter.get_value());
+        assert_eq!(vec!["arg1".to_string(), "arg2".to_string()], arguments.get_other_arguments().clone());
+    }
+
+    #[test]
+    fn test_non_empty_string_parameter() {
+        let string_parameter = StringParameter::new("init");
+        let name_parameter = NonEmptyStringParameter::new("init");
+        let switches = [
+            Switch::new("test", None, Some("ss"), &string_parameter),
+            Switch::new("name", None, Some("name"), &name_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--ss".to_string(), "".to_string()]).is_ok());
+        assert_eq!("", string_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--name".to_string(), "".to_string()]).is_err());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--name".to_string(), "  ".to_string()]).is_err());
+        assert_eq!("init", name_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--name".to_string(), "cache".to_string()]).is_ok());
+        assert_eq!("cache", name_parameter.get_value());
+    }
+
+    #[test]
+    fn test_expanding_string_parameter() {
+        std::env::set_var("ARGUMENTS_PARSER_TEST_HOME", "/home/test");
+        std::env::remove_var("ARGUMENTS_PARSER_TEST_MISSING");
+        let path_parameter = ExpandingStringParameter::new("");
+        let switches = [Switch::new("path", None, Some("path"), &path_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let result = arguments.build(vec!["--path".to_string(),
+                                          "$ARGUMENTS_PARSER_TEST_HOME/data:${ARGUMENTS_PARSER_TEST_HOME}:$ARGUMENTS_PARSER_TEST_MISSING:$".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!("/home/test/data:/home/test::$", path_parameter.get_value());
+    }
+
+    #[test]
+    fn test_deprecated_switch() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("port", Some('p'), None, &port_parameter).deprecated("use --listen instead"),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-v".to_string()]).is_ok());
+        assert!(arguments.warnings().is_empty());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-p".to_string(), "3333".to_string()]).is_ok());
+        assert_eq!(3333, port_parameter.get_value());
+        assert_eq!(1, arguments.warnings().len());
+        assert_eq!("port", arguments.warnings()[0].get_switch_name());
+        assert_eq!("use --listen instead", arguments.warnings()[0].get_message());
+    }
+
+    #[test]
+    fn test_deprecated_by() {
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("verbose", Some('v'), Some("verb"), &verbose_parameter).deprecated_by("--verbose"),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--verb".to_string()]).is_ok());
+        assert!(verbose_parameter.get_value());
+        assert_eq!(1, arguments.warnings().len());
+        assert_eq!(Some("--verbose"), arguments.warnings()[0].get_replacement());
+        assert_eq!("deprecated, use --verbose instead", arguments.warnings()[0].get_message());
+    }
+
+    #[test]
+    fn test_enum_parameter_default() {
+        let values = vec!["debug".to_string(), "info".to_string()];
+        assert!(EnumParameter::try_new(values.clone(), "info").is_ok());
+        assert!(EnumParameter::try_new(values.clone(), "").is_ok());
+        assert!(EnumParameter::try_new(values, "inf").is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_enum_parameter_invalid_default() {
+        EnumParameter::new(vec!["debug".to_string(), "info".to_string()], "inf");
+    }
+
+    #[test]
+    fn test_extend() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let plugin_parameter = StringParameter::new("");
+        let other_port_parameter = IntParameter::new(1, |v|v>0);
+        let switches = [
+            Switch::new("port", Some('p'), Some("port"), &port_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+        ];
+        let plugin_switches = [Switch::new("plugin", None, Some("plugin"), &plugin_parameter)];
+        let conflicting_switches = [Switch::new("plugin port", None, Some("port"), &other_port_parameter)];
+        let mut arguments = Arguments::try_new("cache", &switches, None).unwrap();
+        assert!(arguments.extend(&plugin_switches).is_ok());
+        assert!(arguments.extend(&conflicting_switches).is_err());
+        assert!(arguments.extend(&plugin_switches).is_err());
+        let result = arguments.build(vec!["-p".to_string(), "3333".to_string(),
+                                          "--plugin".to_string(), "test".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!(3333, port_parameter.get_value());
+        assert_eq!("test", plugin_parameter.get_value());
+        assert!(Arguments::try_new("cache", &[switches[0].clone(), conflicting_switches[0].clone()], None).is_err());
+    }
+
+    #[test]
+    fn test_value_string() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let max_memory_parameter = SizeParameter::new(1024 * 1024 * 1024, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let string_parameter = StringParameter::new("init");
+        let enum_parameter = EnumParameter::new(vec!["value".to_string()], "");
+        let switches = [
+            Switch::new("port", Some('p'), None, &port_parameter),
+            Switch::new("maximum_memory", Some('m'), None, &max_memory_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+            Switch::new("test", None, Some("ss"), &string_parameter),
+            Switch::new("test_enum", Some('e'), None, &enum_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert_eq!("false", verbose_parameter.value_string());
+        let result = arguments.build(vec![
+            "-p".to_string(), "3333".to_string(),
+            "-m".to_string(), "1M".to_string(),
+            "-v".to_string(),
+            "--ss".to_string(), "test".to_string(),
+            "-e".to_string(), "value".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!("3333", port_parameter.value_string());
+        assert_eq!("1048576", max_memory_parameter.value_string());
+        assert_eq!("true", verbose_parameter.value_string());
+        assert_eq!("test", string_parameter.value_string());
+        assert_eq!("value", enum_parameter.value_string());
+    }
+
+    #[test]
+    fn test_usage_categories() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let host_parameter = StringParameter::new("localhost");
+        let log_parameter = StringParameter::new("");
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("port", Some('p'), None, &port_parameter).category("Networking"),
+            Switch::new("log_file", None, Some("log"), &log_parameter).category("Logging"),
+            Switch::new("host", Some('h'), None, &host_parameter).category("Networking"),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+        ];
+        let arguments = Arguments::new("cache", &switches, None);
+        assert_eq!("Usage: cache [-v] [-p PORT] [--log LOG_FILE] [-h HOST]\nOptions:\n -v - verbose\n\
+                    Networking:\n -h string - host\n -p int - port\n\
+                    Logging:\n --log string - log_file\n", arguments.usage_string());
+    }
+
+    #[test]
+    fn test_escaped_positional() {
+        let verbose_parameter = BoolParameter::new();
+        let switches = [Switch::new("verbose", Some('v'), None, &verbose_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let result = arguments.build(vec!["\\-weird".to_string(), "-v".to_string(), "\\x".to_string()]);
+        assert!(result.is_ok());
+        assert!(verbose_parameter.get_value());
+        assert_eq!(vec!["-weird".to_string(), "\\x".to_string()], arguments.get_other_arguments().clone());
+    }
+
+    #[test]
+    fn test_range_set_parameter() {
+        let cpus_parameter = RangeSetParameter::new(vec![0]);
+        let switches = [Switch::new("cpus", None, Some("cpus"), &cpus_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--cpus".to_string(), "7-8,0-3,5".to_string()]).is_ok());
+        assert_eq!(vec![0, 1, 2, 3, 5, 7, 8], cpus_parameter.get_values());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--cpus".to_string(), "5-3".to_string()]).is_err());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--cpus".to_string(), "1,a".to_string()]).is_err());
+        assert_eq!(vec![0, 1, 2, 3, 5, 7, 8], cpus_parameter.get_values());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--cpus".to_string(), "0-9223372036854775806".to_string()]).is_err());
+        assert!(arguments.build(vec!["--cpus".to_string(), "0-65535,5".to_string()]).is_err());
+        assert!(arguments.build(vec!["--cpus".to_string(), "0-65535".to_string()]).is_ok());
+        assert_eq!(65536, cpus_parameter.get_values().len());
+    }
+
+    #[test]
+    fn test_fixed_csv_parameter() {
+        let coords_parameter: FixedCsvParameter<isize> = FixedCsvParameter::new(3, vec![0, 0, 0]);
+        let switches = [Switch::new("coords", None, Some("coords"), &coords_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--coords".to_string(), "1,2,3".to_string()]).is_ok());
+        assert_eq!(vec![1, 2, 3], coords_parameter.get_values());
+        assert_eq!("1,2,3", coords_parameter.value_string());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--coords".to_string(), "1,2".to_string()]).is_err());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--coords".to_string(), "1,a,3".to_string()]).is_err());
+        assert_eq!(vec![1, 2, 3], coords_parameter.get_values());
+    }
+
+    #[test]
+    fn test_value_expected_error() {
+        let string_parameter = StringParameter::new("init");
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let switches = [
+            Switch::new("test", None, Some("ss"), &string_parameter),
+            Switch::new("port", Some('p'), None, &port_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let result = arguments.build(vec!["--ss".to_string()]);
+        assert_eq!("value expected for switch --ss", result.unwrap_err().to_string());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let result = arguments.build(vec!["--ss".to_string(), "test".to_string(), "-p".to_string()]);
+        assert_eq!("value expected for switch -p", result.unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_toggle_group() {
+        let level = ToggleGroup::new(ToggleMode::LastWins);
+        let verbose_parameter = level.on();
+        let quiet_parameter = level.off();
+        let switches = [
+            Switch::new("verbose", None, Some("verbose"), &verbose_parameter),
+            Switch::new("quiet", None, Some("quiet"), &quiet_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert_eq!(0, level.get_value());
+        assert!(arguments.build(vec!["--verbose".to_string(), "--quiet".to_string()]).is_ok());
+        assert_eq!(-1, level.get_value());
+
+        let level = ToggleGroup::new(ToggleMode::ErrorOnConflict);
+        let verbose_parameter = level.on();
+        let quiet_parameter = level.off();
+        let switches = [
+            Switch::new("verbose", None, Some("verbose"), &verbose_parameter),
+            Switch::new("quiet", None, Some("quiet"), &quiet_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--verbose".to_string(), "--verbose".to_string()]).is_ok());
+        assert_eq!(1, level.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--verbose".to_string(), "--quiet".to_string()]).is_err());
+        assert!(arguments.build(vec![]).is_ok());
+        assert!(arguments.build(vec!["--quiet".to_string()]).is_ok());
+        assert_eq!(-1, level.get_value());
+        assert!(arguments.build(vec!["--verbose".to_string()]).is_ok());
+        assert_eq!(1, level.get_value());
+    }
+
+    #[test]
+    fn test_cross_validator() {
+        let min_parameter = IntParameter::new(1, |v|v>0);
+        let max_parameter = IntParameter::new(10, |v|v>0);
+        let switches = [
+            Switch::new("min", None, Some("min"), &min_parameter),
+            Switch::new("max", None, Some("max"), &max_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        arguments.add_cross_validator(|| if max_parameter.get_value() >= min_parameter.get_value() {
+            Ok(())
+        } else {
+            Err("max should be greater or equal to min".to_string())
+        });
+        let result = arguments.build(vec!["--min".to_string(), "5".to_string(), "--max".to_string(), "3".to_string()]);
+        assert_eq!("max should be greater or equal to min", result.unwrap_err().to_string());
+        let result = arguments.build(vec!["--min".to_string(), "5".to_string(), "--max".to_string(), "5".to_string()]);
+        assert!(result.is_ok());
+    }
+
+    #[derive(Default)]
+    struct Config {
+        port: isize,
+        verbose: bool,
+        name: String,
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let mut config = Config { port: 6379, name: "main".to_string(), ..Default::default() };
+        let port_parameter = IntParameter::new(config.port, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let name_parameter = StringParameter::new(&config.name);
+        let switches = [
+            Switch::new("port", Some('p'), None, &port_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+            Switch::new("name", None, Some("name"), &name_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-v".to_string(), "-p".to_string(), "3333".to_string()]).is_ok());
+        config.port = port_parameter.get_value();
+        config.verbose = verbose_parameter.get_value();
+        config.name = name_parameter.get_value();
+        assert_eq!(3333, config.port);
+        assert!(config.verbose);
+        assert_eq!("main", config.name);
+    }
+
+    #[test]
+    fn test_nary_parameter() {
+        let point_parameter: NaryParameter<isize> = NaryParameter::new(3, vec![0, 0, 0]);
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("point", None, Some("point"), &point_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, Some(vec!["arg1".to_string()]));
+        let result = arguments.build(vec!["--point".to_string(), "1".to_string(), "2".to_string(), "3".to_string(),
+                                          "-v".to_string(), "arg1".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!(vec![1, 2, 3], point_parameter.get_values());
+        assert!(verbose_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let result = arguments.build(vec!["--point".to_string(), "1".to_string(), "2".to_string()]);
+        assert_eq!("value expected for switch --point", result.unwrap_err().to_string());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let result = arguments.build(vec!["--point".to_string(), "1".to_string(), "a".to_string(), "3".to_string()]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_enum_parameter_abbreviated_values() {
+        let values = vec!["info".to_string(), "debug".to_string(), "default".to_string()];
+        let level_parameter = EnumParameter::new(values.clone(), "info").allow_abbreviated_values();
+        let strict_level_parameter = EnumParameter::new(values, "info");
+        let switches = [
+            Switch::new("level", None, Some("level"), &level_parameter),
+            Switch::new("strict_level", None, Some("strict_level"), &strict_level_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--level".to_string(), "deb".to_string()]).is_ok());
+        assert_eq!("debug", level_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--level".to_string(), "de".to_string()]).is_err());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--level".to_string(), "warn".to_string()]).is_err());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--strict_level".to_string(), "deb".to_string()]).is_err());
+        assert_eq!("debug", level_parameter.get_value());
+        assert_eq!("info", strict_level_parameter.get_value());
+    }
+
+    #[test]
+    fn test_write_usage() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let switches = [Switch::new("port", Some('p'), Some("port"), &port_parameter)];
+        let arguments = Arguments::new("cache", &switches, Some(vec!["arg1".to_string()]));
+        let mut buffer: Vec<u8> = Vec::new();
+        assert!(arguments.write_usage(&mut buffer).is_ok());
+        assert_eq!("Usage: cache [-p PORT] arg1\n -p (or --port) int - port\n\n", String::from_utf8(buffer).unwrap());
+    }
+
+    #[test]
+    fn test_end_of_options() {
+        let verbose_parameter = BoolParameter::new();
+        let switches = [Switch::new("verbose", Some('v'), None, &verbose_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--".to_string(), "--".to_string(), "foo".to_string()]).is_ok());
+        assert_eq!(vec!["--".to_string(), "foo".to_string()], arguments.get_other_arguments().clone());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["arg1".to_string(), "--".to_string(), "-v".to_string()]).is_ok());
+        assert!(!verbose_parameter.get_value());
+        assert_eq!(vec!["arg1".to_string(), "-v".to_string()], arguments.get_other_arguments().clone());
+    }
+
+    struct ColorParameter {
+        value: RefCell<String>,
+    }
+
+    impl ValueHandler for ColorParameter {
+        fn parse_value(&self, value: &str) -> bool {
+            *self.value.borrow_mut() = value.to_string();
+            true
+        }
+
+        fn requires_value(&self) -> bool {
+            true
+        }
+
+        fn set_value(&self) {
+            *self.value.borrow_mut() = "auto".to_string();
+        }
+
+        fn value_type(&self) -> String {
+            " color".to_string()
+        }
+
+        fn value_string(&self) -> String {
+            self.value.borrow().clone()
+        }
+    }
+
+    #[test]
+    fn test_switch_takes_value() {
+        let color_parameter = ColorParameter { value: RefCell::new("never".to_string()) };
+        let switches = [Switch::new("color", Some('c'), None, &color_parameter).takes_value(false)];
+        let mut arguments = Arguments::new("cache", &switches, Some(vec!["arg1".to_string()]));
+        assert!(arguments.build(vec!["-c".to_string(), "arg1".to_string()]).is_ok());
+        assert_eq!("auto", color_parameter.value_string());
+        assert_eq!("Usage: cache [-c] arg1\n -c - color\n", arguments.usage_string());
+    }
+
+    #[test]
+    fn test_int_parameter_errors() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let switches = [Switch::new("port", Some('p'), None, &port_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let error = arguments.build(vec!["-p".to_string(), "99999999999999999999999".to_string()]).unwrap_err();
+        assert_eq!(ParseErrorKind::ValueOutOfRange, ParseError::from_error(&error).unwrap().kind());
+        assert_eq!("port value out of range", error.to_string());
+        let error = arguments.build(vec!["-p".to_string(), "abc".to_string()]).unwrap_err();
+        assert_eq!(ParseErrorKind::NotANumber, ParseError::from_error(&error).unwrap().kind());
+        assert_eq!("port value is not a number", error.to_string());
+        let error = arguments.build(vec!["-p".to_string(), "-1".to_string()]).unwrap_err();
+        assert_eq!(ParseErrorKind::InvalidValue, ParseError::from_error(&error).unwrap().kind());
+        assert_eq!(Some("port"), ParseError::from_error(&error).unwrap().switch());
+    }
+
+    #[test]
+    fn test_secret_parameter() {
+        let password_parameter = SecretParameter::with_reader("Password: ", |prompt| {
+            assert_eq!("Password: ", prompt);
+            Ok("secret".to_string())
+        });
+        let failing_parameter = SecretParameter::with_reader("Token: ",
+            |_| Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "no input")));
+        let switches = [
+            Switch::new("password", None, Some("password"), &password_parameter),
+            Switch::new("token", None, Some("token"), &failing_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, Some(vec!["arg1".to_string()]));
+        assert!(arguments.build(vec!["--password".to_string(), "arg1".to_string()]).is_ok());
+        assert_eq!("secret", password_parameter.get_value());
+        assert_eq!("*****", password_parameter.value_string());
+        assert!(arguments.build(vec!["--token".to_string(), "arg1".to_string()]).is_err());
+        assert!(arguments.build(vec!["arg1".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn test_count_parameter() {
+        let verbose_parameter = CountParameter::new(Some(3));
+        let quiet_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+            Switch::new("quiet", Some('q'), None, &quiet_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-vvvvv".to_string(), "-qv".to_string()]).is_ok());
+        assert_eq!(3, verbose_parameter.get_value());
+        assert!(quiet_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-vx".to_string()]).is_err());
+
+        let verbose_parameter = CountParameter::new(Some(3)).error_on_overflow();
+        let switches = [Switch::new("verbose", Some('v'), None, &verbose_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-vvvv".to_string()]).is_err());
+        assert!(arguments.build(vec![]).is_ok());
+        assert_eq!(0, verbose_parameter.get_value());
+    }
+
+    #[test]
+    fn test_arguments_validator() {
+        let output_parameter = StringParameter::new("");
+        let switches = [Switch::new("output", Some('o'), None, &output_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        arguments.add_validator(Box::new(|arguments| {
+            if arguments.get_other_arguments().len() > 1 && arguments.was_present("output") {
+                Err("output can be used with a single input file only".to_string())
+            } else {
+                Ok(())
+            }
+        }));
+        assert!(arguments.build(vec!["-o".to_string(), "out".to_string(), "in1".to_string()]).is_ok());
+        assert!(arguments.was_present("output"));
+        let result = arguments.build(vec!["in1".to_string(), "in2".to_string()]);
+        assert!(result.is_ok());
+        assert!(!arguments.was_present("output"));
+        let result = arguments.build(vec!["in1".to_string(), "-o".to_string(), "out".to_string(), "in2".to_string()]);
+        assert_eq!("output can be used with a single input file only", result.unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_temperature_parameter() {
+        let temperature_parameter = TemperatureParameter::new(20.0);
+        let switches = [Switch::new("temperature", None, Some("temp"), &temperature_parameter)];
+        for value in ["100C", "212F", "373.15K"] {
+            let mut arguments = Arguments::new("cache", &switches, None);
+            temperature_parameter.parse_value("0C");
+            assert!(arguments.build(vec!["--temp".to_string(), value.to_string()]).is_ok());
+            assert!((temperature_parameter.get_value() - 100.0).abs() < 1e-9, "{}", value);
+        }
+        for value in ["100", "100X", "C", "-10K"] {
+            let mut arguments = Arguments::new("cache", &switches, None);
+            assert!(arguments.build(vec!["--temp".to_string(), value.to_string()]).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_usage_for() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("port", Some('p'), Some("listen_port"), &port_parameter)
+                .description("TCP port to listen on.")
+                .example("-p 8080"),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+        ];
+        let arguments = Arguments::new("cache", &switches, None);
+        let help = arguments.usage_for("port").unwrap();
+        assert_eq!("port\n  switches: -p, --listen_port\n  type: int\n  default: 6379\n  example: -p 8080\n  \
+                    TCP port to listen on.\n", help);
+        assert_eq!(Some(help), arguments.usage_for("listen_port"));
+        assert_eq!(Some("verbose\n  switches: -v\n".to_string()), arguments.usage_for("verbose"));
+        assert_eq!(None, arguments.usage_for("unknown"));
+    }
+
+    #[test]
+    fn test_attached_values() {
+        let name_parameter = StringParameter::new("init");
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("name", None, Some("name"), &name_parameter),
+            Switch::new("port", Some('p'), Some("port"), &port_parameter),
+            Switch::new("verbose", Some('v'), Some("verbose"), &verbose_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--name=".to_string(), "--port=3333".to_string()]).is_ok());
+        assert_eq!("", name_parameter.get_value());
+        assert_eq!(3333, port_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let error = arguments.build(vec!["--port=".to_string()]).unwrap_err();
+        assert_eq!(ParseErrorKind::NotANumber, ParseError::from_error(&error).unwrap().kind());
+        assert_eq!("port value is not a number", error.to_string());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let error = arguments.build(vec!["--verbose=true".to_string()]).unwrap_err();
+        assert_eq!(ParseErrorKind::UnexpectedValue, ParseError::from_error(&error).unwrap().kind());
+    }
+
+    #[test]
+    fn test_messages() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let verbose_parameter = CountParameter::new(Some(1)).error_on_overflow();
+        let switches = [Switch::new("port", Some('p'), None, &port_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        arguments.set_messages(Messages {
+            usage: "Verwendung: ".to_string(),
+            unknown_switch: "unbekannte Option".to_string(),
+            invalid_value: "ungültiger Wert für {}".to_string(),
+            ..Default::default()
+        });
+        assert_eq!("unbekannte Option", arguments.build(vec!["-x".to_string()]).unwrap_err().to_string());
+        assert_eq!("ungültiger Wert für port",
+                   arguments.build(vec!["-p".to_string(), "-1".to_string()]).unwrap_err().to_string());
+        // handler messages are not part of Messages
+        assert_eq!("switch can be used at most 1 times",
+                   arguments.build(vec!["-vv".to_string()]).unwrap_err().to_string());
+        assert!(arguments.usage_string().starts_with("Verwendung: cache [-v] [-p PORT]\n"));
+    }
+
+    #[test]
+    fn test_commands() {
+        let verbose_parameter = BoolParameter::new();
+        let switches = [Switch::new("verbose", Some('v'), None, &verbose_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, Some(vec!["key".to_string()]));
+        arguments.set_commands(vec!["get".to_string(), "set".to_string()]);
+        assert!(arguments.build(vec!["-v".to_string(), "get".to_string(), "key1".to_string()]).is_ok());
+        assert_eq!(Some("get"), arguments.command());
+        assert_eq!(vec!["key1".to_string()], arguments.get_other_arguments().clone());
+        let mut arguments = Arguments::new("cache", &switches, Some(vec!["key".to_string()]));
+        arguments.set_commands(vec!["get".to_string(), "set".to_string()]);
+        let error = arguments.build(vec!["delete".to_string(), "key1".to_string()]).unwrap_err();
+        assert_eq!("unknown command delete", error.to_string());
+        assert_eq!(None, arguments.command());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        arguments.set_commands(vec!["get".to_string(), "set".to_string()]);
+        let error = arguments.build(vec!["-v".to_string()]).unwrap_err();
+        assert_eq!(ParseErrorKind::CommandExpected, ParseError::from_error(&error).unwrap().kind());
+    }
+
+    #[test]
+    fn test_build_from_line() {
+        let name_parameter = StringParameter::new("");
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let switches = [
+            Switch::new("name", None, Some("name"), &name_parameter),
+            Switch::new("port", Some('p'), None, &port_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build_from_line("  --name \"hello world\"  -p 3333 ''").is_ok());
+        assert_eq!("hello world", name_parameter.get_value());
+        assert_eq!(3333, port_parameter.get_value());
+        assert_eq!(vec!["".to_string()], arguments.get_other_arguments().clone());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build_from_line(r#"--name "say \"hi\"""#).is_ok());
+        assert_eq!("say \"hi\"", name_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build_from_line(r#"--name it\'s\ me"#).is_ok());
+        assert_eq!("it's me", name_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let error = arguments.build_from_line("--name 'unterminated").unwrap_err();
+        assert_eq!(ParseErrorKind::UnterminatedQuote, ParseError::from_error(&error).unwrap().kind());
+    }
+
+    #[test]
+    fn test_file_set_enum_parameter() {
+        let file_name = std::env::temp_dir().join("arguments_parser_test_users.txt");
+        std::fs::write(&file_name, "alice\n  bob  \n\n").unwrap();
+        let user_parameter = FileSetEnumParameter::new(file_name.to_str().unwrap(), "").unwrap();
+        let switches = [Switch::new("user", Some('u'), None, &user_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-u".to_string(), "bob".to_string()]).is_ok());
+        assert_eq!("bob", user_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-u".to_string(), "eve".to_string()]).is_err());
+        std::fs::remove_file(&file_name).unwrap();
+        assert!(FileSetEnumParameter::new(file_name.to_str().unwrap(), "").is_err());
+    }
+
+    #[test]
+    fn test_short_switch_cluster_with_value() {
+        let verbose_parameter = BoolParameter::new();
+        let extra_parameter = BoolParameter::new();
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let switches = [
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+            Switch::new("extra", Some('x'), None, &extra_parameter),
+            Switch::new("port", Some('p'), None, &port_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, Some(vec!["arg1".to_string()]));
+        assert!(arguments.build(vec!["-vxp3333".to_string(), "arg1".to_string()]).is_ok());
+        assert!(verbose_parameter.get_value());
+        assert!(extra_parameter.get_value());
+        assert_eq!(3333, port_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, Some(vec!["arg1".to_string()]));
+        assert!(arguments.build(vec!["-vxp".to_string(), "4444".to_string(), "arg1".to_string()]).is_ok());
+        assert_eq!(4444, port_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-p5555".to_string()]).is_ok());
+        assert_eq!(5555, port_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-vp".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_positional_as() {
+        let mut arguments = Arguments::new("cache", &[], Some(vec!["count".to_string(), "name".to_string()]));
+        assert!(arguments.build(vec!["42".to_string(), "abc".to_string()]).is_ok());
+        assert_eq!(Ok(42), arguments.positional_as::<u32>(0));
+        let error = arguments.positional_as::<u32>(1).unwrap_err();
+        assert_eq!(ParseErrorKind::InvalidValue, error.kind());
+        assert_eq!("invalid name value", error.to_string());
+        assert_eq!(ParseErrorKind::IncorrectArgumentCount, arguments.positional_as::<u32>(2).unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_flag() {
+        let verbose_parameter = BoolParameter::new();
+        let quiet_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+            Switch::new("quiet", Some('q'), None, &quiet_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-v".to_string()]).is_ok());
+        assert!(arguments.flag('v'));
+        assert!(!arguments.flag('q'));
+        assert!(!arguments.flag('x'));
+        assert!(arguments.build(vec!["-q".to_string()]).is_ok());
+        assert!(!arguments.flag('v'));
+        assert!(arguments.flag('q'));
+    }
+
+    #[test]
+    fn test_strict_values() {
+        let message_parameter = StringParameter::new("");
+        let verbose_parameter = BoolParameter::new();
+        let port_parameter = IntParameter::new(6379, validators::positive);
+        let switches = [
+            Switch::new("message", None, Some("message"), &message_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+            Switch::new("port", None, Some("port"), &port_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--message".to_string(), "-v".to_string()]).is_ok());
+        assert_eq!("-v", message_parameter.get_value());
+        assert!(!verbose_parameter.get_value());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        arguments.set_strict_values(true);
+        let error = arguments.build(vec!["--message".to_string(), "-v".to_string()]).unwrap_err();
+        assert_eq!(ParseErrorKind::ValueExpected, ParseError::from_error(&error).unwrap().kind());
+        assert_eq!("value expected for switch --message", error.to_string());
+        assert!(arguments.build(vec!["--message".to_string(), "-x".to_string()]).is_ok());
+        assert_eq!("-x", message_parameter.get_value());
+        for value in ["--port=5", "-vx", "--port"] {
+            let error = arguments.build(vec!["--message".to_string(), value.to_string()]).unwrap_err();
+            assert_eq!(ParseErrorKind::ValueExpected, ParseError::from_error(&error).unwrap().kind(), "{}", value);
+        }
+        assert!(arguments.build(vec!["--message".to_string(), "--colour=red".to_string()]).is_ok());
+        assert_eq!("--colour=red", message_parameter.get_value());
+    }
+
+    #[test]
+    fn test_explicit_bool_parameter() {
+        let cache_parameter = ExplicitBoolParameter::new(false);
+        let power_parameter = ExplicitBoolParameter::with_words(false, vec!["on".to_string()], vec!["off".to_string()]);
+        let switches = [
+            Switch::new("cache", None, Some("cache"), &cache_parameter),
+            Switch::new("power", None, Some("power"), &power_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let result = arguments.build(vec!["--cache".to_string(), "Yes".to_string(),
+                                          "--power".to_string(), "ON".to_string()]);
+        assert!(result.is_ok());
+        assert!(cache_parameter.get_value());
+        assert!(power_parameter.get_value());
+        assert!(arguments.build(vec!["--power".to_string(), "off".to_string()]).is_ok());
+        assert!(!power_parameter.get_value());
+        assert!(arguments.build(vec!["--power".to_string(), "yes".to_string()]).is_err());
+        assert!(arguments.build(vec!["--cache".to_string(), "maybe".to_string()]).is_err());
+        assert_eq!("Usage: cache [--cache CACHE] [--power POWER]\n --cache true|false - cache\n \
+                    --power on|off - power\n", arguments.usage_string());
+    }
+
+    #[test]
+    fn test_value_source() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("port", Some('p'), None, &port_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-p".to_string(), "3333".to_string()]).is_ok());
+        assert_eq!(ValueSource::Cli, arguments.value_source("port"));
+        assert_eq!(ValueSource::Default, arguments.value_source("verbose"));
+        assert!(arguments.build(vec!["-v".to_string()]).is_ok());
+        assert_eq!(ValueSource::Default, arguments.value_source("port"));
+        assert_eq!(ValueSource::Cli, arguments.value_source("verbose"));
+    }
+
+    #[test]
+    fn test_last_wins_key_value_parameter() {
+        let set_parameter = LastWinsKeyValueParameter::new(HashMap::new());
+        let switches = [Switch::new("set", None, Some("set"), &set_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let result = arguments.build(vec!["--set".to_string(), "a=1".to_string(),
+                                          "--set".to_string(), "b=x=y".to_string(),
+                                          "--set".to_string(), "a=2".to_string()]);
+        assert!(result.is_ok());
+        let values = set_parameter.get_values();
+        assert_eq!(2, values.len());
+        assert_eq!(Some(&"2".to_string()), values.get("a"));
+        assert_eq!(Some(&"x=y".to_string()), values.get("b"));
+        assert_eq!("a=2,b=x=y", set_parameter.value_string());
+        assert!(arguments.build(vec!["--set".to_string(), "a".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_bare_switches() {
+        let verbose_parameter = BoolParameter::new();
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let switches = [
+            Switch::new("verbose", Some('v'), Some("verbose"), &verbose_parameter),
+            Switch::new("port", Some('p'), Some("port"), &port_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["verbose".to_string()]).is_ok());
+        assert!(!verbose_parameter.get_value());
+        assert_eq!(vec!["verbose".to_string()], arguments.get_other_arguments().clone());
+        let mut arguments = Arguments::new("cache", &switches, None);
+        arguments.set_bare_switches(true);
+        let result = arguments.build(vec!["verbose".to_string(), "port".to_string(), "3333".to_string(),
+                                          "verb".to_string(), "--".to_string(), "port".to_string()]);
+        assert!(result.is_ok());
+        assert!(verbose_parameter.get_value());
+        assert_eq!(3333, port_parameter.get_value());
+        assert_eq!(vec!["verb".to_string(), "port".to_string()], arguments.get_other_arguments().clone());
+    }
+
+    #[test]
+    fn test_pointer_parameter() {
+        let address_parameter = PointerParameter::new(0);
+        let switches = [Switch::new("address", None, Some("addr"), &address_parameter)];
+        let mut arguments = Arguments::new("debugger", &switches, None);
+        assert!(arguments.build(vec!["--addr".to_string(), "0x7fff1234".to_string()]).is_ok());
+        assert_eq!(0x7fff1234, address_parameter.get_value());
+        assert_eq!("0x7fff1234", address_parameter.value_string());
+        for value in ["7fff1234", "0x", "0x12g4", "0x+12", "0x10000000000000000"] {
+            let mut arguments = Arguments::new("debugger", &switches, None);
+            assert!(arguments.build(vec!["--addr".to_string(), value.to_string()]).is_err(), "{}", value);
+        }
+        assert_eq!(0x7fff1234, address_parameter.get_value());
+    }
+
+    #[test]
+    fn test_switch_new_owned() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let prefix = "cache";
+        let switches = [Switch::new_owned("port", None, Some(format!("{}-port", prefix)), &port_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--cache-port".to_string(), "3333".to_string()]).is_ok());
+        assert_eq!(3333, port_parameter.get_value());
+    }
+
+    #[test]
+    fn test_switch_builder() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let switches = [Switch::for_handler("port", &port_parameter).short('p').long("port")];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert_eq!("Usage: cache [-p PORT]\n -p (or --port) int - port\n", arguments.usage_string());
+        assert!(arguments.build(vec!["--port".to_string(), "3333".to_string()]).is_ok());
+        assert_eq!(3333, port_parameter.get_value());
+        assert!(arguments.build(vec!["-p".to_string(), "4444".to_string()]).is_ok());
+        assert_eq!(4444, port_parameter.get_value());
+    }
+
+    #[test]
+    fn test_collect_trailing() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let switches = [Switch::new("port", Some('p'), None, &port_parameter)];
+        let mut arguments = Arguments::new("run", &switches, None);
+        arguments.set_collect_trailing(true);
+        let args = ["-p", "80", "--", "ls", "-la", "/tmp"].iter().map(|a| a.to_string()).collect();
+        assert!(arguments.build(args).is_ok());
+        assert_eq!(80, port_parameter.get_value());
+        assert_eq!(["ls".to_string(), "-la".to_string(), "/tmp".to_string()], arguments.get_trailing());
+        assert!(arguments.get_other_arguments().is_empty());
+        let mut arguments = Arguments::new("run", &switches, None);
+        assert!(arguments.build(vec!["--".to_string(), "ls".to_string()]).is_ok());
+        assert!(arguments.get_trailing().is_empty());
+        assert_eq!(vec!["ls".to_string()], arguments.get_other_arguments().clone());
+    }
+
+    #[test]
+    fn test_short_switch_validation() {
+        let verbose_parameter = BoolParameter::new();
+        assert!(Switch::try_new("verbose", Some('v'), None, &verbose_parameter).is_ok());
+        assert!(Switch::try_new("verbose", Some('1'), None, &verbose_parameter).is_ok());
+        assert!(Switch::try_new("verbose", None, Some("verbose"), &verbose_parameter).is_ok());
+        assert!(Switch::try_new("verbose", Some(' '), None, &verbose_parameter).is_err());
+        assert!(Switch::try_new("verbose", Some('\t'), None, &verbose_parameter).is_err());
+        assert!(Switch::try_new("verbose", Some('-'), None, &verbose_parameter).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_short_switch_space() {
+        Switch::new("verbose", Some(' '), None, &BoolParameter::new());
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+    struct Options {
+        level: u32,
+        name: String,
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_struct_parameter() {
+        let options_parameter: crate::StructParameter<Options> = crate::StructParameter::new(None);
+        let switches = [Switch::new("options", None, Some("opts"), &options_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--opts".to_string(), "level=3,name=fast".to_string()]).is_ok());
+        assert_eq!(Some(Options { level: 3, name: "fast".to_string() }), options_parameter.get_value());
+        assert!(arguments.build(vec!["--opts".to_string(), "level=high,name=fast".to_string()]).is_err());
+        assert!(arguments.build(vec!["--opts".to_string(), "level=3".to_string()]).is_err());
+        assert!(arguments.build(vec!["--opts".to_string(), "level".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_help_request() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let switches = [Switch::new("port", Some('p'), None, &port_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, Some(vec!["arg1".to_string()]));
+        assert!(arguments.build(vec!["--help".to_string()]).is_err());
+        arguments.enable_help();
+        assert!(arguments.build(vec!["--help=port".to_string()]).is_ok());
+        assert_eq!(Some(&HelpRequest::Switch("port".to_string())), arguments.help_request());
+        assert!(arguments.usage_for("port").is_some());
+        assert!(arguments.build(vec!["-p".to_string(), "3333".to_string(), "--help".to_string()]).is_ok());
+        assert_eq!(Some(&HelpRequest::Full), arguments.help_request());
+        assert!(arguments.build(vec!["arg1".to_string()]).is_ok());
+        assert_eq!(None, arguments.help_request());
+    }
+
+    #[test]
+    fn test_usage_colored_string() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("port", Some('p'), Some("port"), &port_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+        ];
+        let arguments = Arguments::new("cache", &switches, None);
+        assert_eq!("Usage: cache [-v] [-p PORT]\n \
+                    \x1b[32m-p\x1b[0m (or \x1b[32m--port\x1b[0m) \x1b[33mint\x1b[0m - port\n \
+                    \x1b[32m-v\x1b[0m - verbose\n", arguments.usage_colored_string());
+        assert!(!arguments.usage_string().contains('\x1b'));
+    }
+
+    #[test]
+    fn test_alias_switch() {
+        let level_parameter = IntParameter::new(5, |v|(0..=9).contains(&v));
+        let fast_parameter = AliasSwitch::new(&level_parameter, "9");
+        let broken_parameter = AliasSwitch::new(&level_parameter, "10");
+        let switches = [
+            Switch::new("level", Some('l'), None, &level_parameter),
+            Switch::new("fast", None, Some("fast"), &fast_parameter),
+            Switch::new("broken", None, Some("broken"), &broken_parameter),
+        ];
+        let mut arguments = Arguments::new("zip", &switches, None);
+        assert!(arguments.build(vec!["--fast".to_string()]).is_ok());
+        assert_eq!(9, level_parameter.get_value());
+        assert!(fast_parameter.get_value());
+        assert_eq!(Some(true), arguments.get_value::<bool>("fast"));
+        assert!(arguments.build(vec!["--fast".to_string(), "-l".to_string(), "3".to_string()]).is_ok());
+        assert_eq!(3, level_parameter.get_value());
+        assert!(arguments.build(vec!["--broken".to_string()]).is_err());
+        assert!(arguments.build(vec!["-l".to_string(), "4".to_string()]).is_ok());
+        assert_eq!(4, level_parameter.get_value());
+        assert!(!fast_parameter.get_value());
+        assert_eq!("false", fast_parameter.value_string());
+        assert_eq!(Some(false), arguments.get_value::<bool>("fast"));
+    }
+
+    #[test]
+    fn test_build_with_callback() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("port", Some('p'), None, &port_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let mut events = Vec::new();
+        let args = ["-vp", "3333", "arg1", "-p", "x"].map(|a| a.to_string());
+        assert!(arguments.build_with_callback(&args, |e| events.push(e)).is_err());
+        assert_eq!(vec![
+            ParseEvent::SwitchSeen("verbose".to_string()),
+            ParseEvent::SwitchSeen("port".to_string()),
+            ParseEvent::ValueParsed { switch: "port".to_string(), value: "3333".to_string() },
+            ParseEvent::PositionalSeen("arg1".to_string()),
+            ParseEvent::SwitchSeen("port".to_string()),
+            ParseEvent::Error(ParseError::new(ParseErrorKind::NotANumber, Some("port"), "port value is not a number")),
+        ], events);
+    }
+
+    #[test]
+    fn test_choice_or_free_parameter() {
+        let output_parameter = ChoiceOrFreeParameter::new(vec!["stdout".to_string(), "stderr".to_string()], "stdout",
+                                                          |v| !v.is_empty());
+        let switches = [Switch::new("output", Some('o'), None, &output_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-o".to_string(), "stderr".to_string()]).is_ok());
+        assert_eq!("stderr", output_parameter.get_value());
+        assert!(output_parameter.is_keyword());
+        assert!(arguments.build(vec!["-o".to_string(), "/tmp/out.txt".to_string()]).is_ok());
+        assert_eq!("/tmp/out.txt", output_parameter.get_value());
+        assert!(!output_parameter.is_keyword());
+        assert!(arguments.build(vec!["-o".to_string(), "".to_string()]).is_err());
+        assert_eq!("Usage: cache [-o OUTPUT]\n -o stderr|stdout|value - output\n", arguments.usage_string());
+    }
+
+    #[test]
+    fn test_signed_adjustment_parameter() {
+        let adjust_parameter = SignedAdjustmentParameter::new(0);
+        let switches = [Switch::new("adjust", None, Some("adjust"), &adjust_parameter)];
+        let mut arguments = Arguments::new("nudge", &switches, None);
+        assert!(arguments.build(vec!["--adjust".to_string(), "+5".to_string()]).is_ok());
+        assert_eq!(5, adjust_parameter.get_value());
+        assert!(adjust_parameter.is_relative());
+        assert_eq!(15, adjust_parameter.apply(10));
+        assert_eq!("+5", adjust_parameter.value_string());
+        assert!(arguments.build(vec!["--adjust".to_string(), "-3".to_string()]).is_ok());
+        assert_eq!(-3, adjust_parameter.get_value());
+        assert!(adjust_parameter.is_relative());
+        assert_eq!(7, adjust_parameter.apply(10));
+        assert_eq!(isize::MIN, adjust_parameter.apply(isize::MIN));
+        assert!(arguments.build(vec!["--adjust".to_string(), "+1".to_string()]).is_ok());
+        assert_eq!(isize::MAX, adjust_parameter.apply(isize::MAX));
+        assert!(arguments.build(vec!["--adjust".to_string(), "5".to_string()]).is_ok());
+        assert_eq!(5, adjust_parameter.get_value());
+        assert!(!adjust_parameter.is_relative());
+        assert_eq!(5, adjust_parameter.apply(10));
+        assert!(arguments.build(vec!["--adjust".to_string(), "+x".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_build_until_unknown() {
+        let verbose_parameter = BoolParameter::new();
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let switches = [
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+            Switch::new("port", Some('p'), Some("port"), &port_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let args: Vec<String> = ["-v", "--port", "3333", "-vx", "-p", "1"].map(|a| a.to_string()).to_vec();
+        assert_eq!(3, arguments.build_until_unknown(args.clone()).unwrap());
+        assert!(verbose_parameter.get_value());
+        assert_eq!(3333, port_parameter.get_value());
+        assert!(arguments.build(args).is_err());
+        let args: Vec<String> = ["-p", "4444", "--unknown=1"].map(|a| a.to_string()).to_vec();
+        assert_eq!(2, arguments.build_until_unknown(args).unwrap());
+        assert_eq!(4444, port_parameter.get_value());
+        let args: Vec<String> = ["-p", "5555"].map(|a| a.to_string()).to_vec();
+        assert_eq!(2, arguments.build_until_unknown(args).unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_file_parameter() {
+        let valid_file = std::env::temp_dir().join("arguments_parser_test_rules.json");
+        let invalid_file = std::env::temp_dir().join("arguments_parser_test_invalid_rules.json");
+        std::fs::write(&valid_file, r#"{"level": 3, "name": "strict"}"#).unwrap();
+        std::fs::write(&invalid_file, r#"{"level": "high"}"#).unwrap();
+        let rules_parameter: crate::JsonFileParameter<Options> = crate::JsonFileParameter::new(None);
+        let switches = [Switch::new("rules", None, Some("rules"), &rules_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--rules".to_string(), valid_file.to_str().unwrap().to_string()]).is_ok());
+        assert_eq!(Some(Options { level: 3, name: "strict".to_string() }), rules_parameter.get_value());
+        assert!(arguments.build(vec!["--rules".to_string(), invalid_file.to_str().unwrap().to_string()]).is_err());
+        std::fs::remove_file(&valid_file).unwrap();
+        std::fs::remove_file(&invalid_file).unwrap();
+        assert!(arguments.build(vec!["--rules".to_string(), valid_file.to_str().unwrap().to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_count_parameter_map() {
+        let buffer_parameter = CountParameter::new(None);
+        let switches = [Switch::new("buffer", Some('B'), None, &buffer_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let base = 4096;
+        assert_eq!(0, buffer_parameter.map(|n| base * n));
+        assert!(arguments.build(vec!["-B".to_string(), "-B".to_string()]).is_ok());
+        assert_eq!(base * 2, buffer_parameter.map(|n| base * n));
+        assert_eq!(base << 2, buffer_parameter.map(|n| base << n));
+    }
+
+    #[test]
+    fn test_duplicate_switch_name() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let other_port_parameter = IntParameter::new(6379, |v|v>0);
         let switches = [
             Switch::new("port", Some('p'), None, &port_parameter),
-            Switch::new("maximum_memory", Some('m'), None, &max_memory_parameter),
+            Switch::new("port", None, Some("port"), &other_port_parameter),
+        ];
+        let error = Arguments::try_new("cache", &switches, None).err().unwrap();
+        assert_eq!("duplicate switch name port", error.to_string());
+        let mut arguments = Arguments::try_new("cache", &switches[..1], None).unwrap();
+        assert!(arguments.extend(&switches[1..]).is_err());
+    }
+
+    #[test]
+    fn test_raw_values() {
+        let size_parameter = SizeParameter::new(0, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let point_parameter: NaryParameter<isize> = NaryParameter::new(2, vec![0, 0]);
+        let name_parameter = StringParameter::new("");
+        let switches = [
+            Switch::new("size", Some('s'), None, &size_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+            Switch::new("point", None, Some("point"), &point_parameter),
+            Switch::new("name", None, Some("name"), &name_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let args = ["-vs1M", "--point", "1", "2"].map(|a| a.to_string()).to_vec();
+        assert!(arguments.build(args).is_ok());
+        let raw_values = arguments.raw_values();
+        assert_eq!(3, raw_values.len());
+        assert_eq!(Some(&"1M".to_string()), raw_values.get("size"));
+        assert_eq!(Some(&"true".to_string()), raw_values.get("verbose"));
+        assert_eq!(Some(&"1 2".to_string()), raw_values.get("point"));
+        assert_eq!(None, raw_values.get("name"));
+    }
+
+    #[test]
+    fn test_size_parameter_u64() {
+        let size_parameter = SizeParameter::new(1024, |v|v>0);
+        let switches = [Switch::new("size", Some('s'), None, &size_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-s".to_string(), "8G".to_string()]).is_ok());
+        assert_eq!(8 * 1024 * 1024 * 1024, size_parameter.get_value_u64());
+        let big = isize::MAX as u64 + 1;
+        assert!(arguments.build(vec!["-s".to_string(), big.to_string()]).is_ok());
+        assert_eq!(big, size_parameter.get_value_u64());
+        assert_eq!(isize::MAX, size_parameter.get_value());
+        assert!(arguments.build(vec!["-s".to_string(), "-1".to_string()]).is_err());
+        assert!(arguments.build(vec!["-s".to_string(), u64::MAX.to_string() + "K"]).is_err());
+        assert_eq!(big, size_parameter.get_value_u64());
+    }
+
+    #[test]
+    fn test_hidden_switch() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let debug_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("port", Some('p'), None, &port_parameter),
+            Switch::new("debug", None, Some("debug"), &debug_parameter).hidden(),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert_eq!("Usage: cache [-p PORT]\n -p int - port\n", arguments.usage_string());
+        assert!(arguments.build(vec!["--debug".to_string()]).is_ok());
+        assert!(debug_parameter.get_value());
+        let switches = [switches[0].clone().category("Networking"), switches[1].clone().category("Debug")];
+        let arguments = Arguments::new("cache", &switches, None);
+        assert_eq!("Usage: cache [-p PORT]\nNetworking:\n -p int - port\n", arguments.usage_string());
+    }
+
+    #[test]
+    fn test_ext_switch_validation() {
+        let level_parameter = IntParameter::new(1, |v|v>0);
+        assert!(Switch::try_new("level", None, Some("bad name"), &level_parameter).is_err());
+        assert!(Switch::try_new("level", None, Some("-level"), &level_parameter).is_err());
+        assert!(Switch::try_new("level", None, Some(""), &level_parameter).is_err());
+        let switches = [Switch::try_new("level", None, Some("log-level-2"), &level_parameter).unwrap()];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--log-level-2".to_string(), "3".to_string()]).is_ok());
+        assert_eq!(3, level_parameter.get_value());
+    }
+
+    #[test]
+    fn test_parsed_switch_count() {
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let verbose_parameter = BoolParameter::new();
+        let name_parameter = StringParameter::new("");
+        let switches = [
+            Switch::new("port", Some('p'), None, &port_parameter),
+            Switch::new("verbose", Some('v'), Some("verbose"), &verbose_parameter),
+            Switch::new("name", None, Some("name"), &name_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec![]).is_ok());
+        assert_eq!(0, arguments.parsed_switch_count());
+        let args = ["-vp", "3333", "--verbose", "--name", "test"].map(|a| a.to_string()).to_vec();
+        assert!(arguments.build(args).is_ok());
+        assert_eq!(3, arguments.parsed_switch_count());
+    }
+
+    #[test]
+    fn test_iso_duration_parameter() {
+        let timeout_parameter = IsoDurationParameter::new(Duration::from_secs(10));
+        let switches = [Switch::new("timeout", None, Some("timeout"), &timeout_parameter)];
+        for (value, seconds) in [("PT1H30M", 5400.0), ("PT45S", 45.0), ("P1DT12H", 129600.0), ("P1W", 604800.0),
+                                 ("PT0.5S", 0.5)] {
+            let mut arguments = Arguments::new("cache", &switches, None);
+            assert!(arguments.build(vec!["--timeout".to_string(), value.to_string()]).is_ok(), "{}", value);
+            assert_eq!(Duration::from_secs_f64(seconds), timeout_parameter.get_value(), "{}", value);
+        }
+        for value in ["P1X", "P", "PT", "PT30", "PT30M1H", "P1Y", "1H", "PT1.2.3S"] {
+            let mut arguments = Arguments::new("cache", &switches, None);
+            assert!(arguments.build(vec!["--timeout".to_string(), value.to_string()]).is_err(), "{}", value);
+        }
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn test_glob_expansion() {
+        let dir = std::env::temp_dir().join("arguments_parser_test_glob");
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.log", "a.log", "c.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let logs = dir.join("*.log").to_str().unwrap().to_string();
+        let missing = dir.join("*.gz").to_str().unwrap().to_string();
+        let mut arguments = Arguments::new("cache", &[], None);
+        arguments.set_glob_expansion(true, true);
+        assert!(arguments.build(vec![logs.clone(), "x".to_string()]).is_ok());
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        assert_eq!(vec![path("a.log"), path("b.log"), "x".to_string()], arguments.get_other_arguments().clone());
+        let mut arguments = Arguments::new("cache", &[], None);
+        arguments.set_glob_expansion(true, true);
+        let error = arguments.build(vec![missing.clone()]).unwrap_err();
+        assert_eq!(ParseErrorKind::NoGlobMatch, ParseError::from_error(&error).unwrap().kind());
+        let mut arguments = Arguments::new("cache", &[], None);
+        arguments.set_glob_expansion(true, false);
+        assert!(arguments.build(vec![missing.clone()]).is_ok());
+        assert_eq!(vec![missing], arguments.get_other_arguments().clone());
+        let mut arguments = Arguments::new("cache", &[], None);
+        assert!(arguments.build(vec![logs.clone()]).is_ok());
+        assert_eq!(vec![logs], arguments.get_other_arguments().clone());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_twice() {
+        let mut arguments = Arguments::new("cache", &[], None);
+        assert!(arguments.build(vec!["in1".to_string(), "in2".to_string()]).is_ok());
+        assert!(arguments.build(vec!["in3".to_string()]).is_ok());
+        assert_eq!(vec!["in3".to_string()], arguments.get_other_arguments().clone());
+        arguments.clear_other_arguments();
+        assert!(arguments.get_other_arguments().is_empty());
+    }
+
+    #[test]
+    fn test_build_twice_with_argument_names() {
+        let verbose_parameter = BoolParameter::new();
+        let debug_parameter = CountParameter::new(Some(3)).error_on_overflow();
+        let switches = [
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+            Switch::new("debug", Some('d'), None, &debug_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, Some(vec!["arg1".to_string(), "arg2".to_string()]));
+        let args = vec!["-v".to_string(), "in1".to_string(), "in2".to_string()];
+        assert!(arguments.build(args.clone()).is_ok());
+        assert!(arguments.build(args).is_ok());
+        assert_eq!(2, arguments.get_other_arguments().len());
+        assert!(arguments.build(vec!["in1".to_string(), "in2".to_string()]).is_ok());
+        assert!(!arguments.was_present("verbose"));
+        let args = vec!["-dd".to_string(), "in1".to_string(), "in2".to_string()];
+        assert!(arguments.build(args.clone()).is_ok());
+        assert!(arguments.build(args).is_ok());
+        assert_eq!(2, debug_parameter.get_value());
+    }
+
+    #[test]
+    fn test_power_of_two_parameter() {
+        let buffer_parameter = PowerOfTwoParameter::new(4096);
+        let switches = [Switch::new("buffer", Some('b'), None, &buffer_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-b".to_string(), "1024".to_string()]).is_ok());
+        assert_eq!(1024, buffer_parameter.get_value());
+        assert!(arguments.build(vec!["-b".to_string(), "1".to_string()]).is_ok());
+        assert_eq!(1, buffer_parameter.get_value());
+        for value in ["1000", "0", "-2"] {
+            let error = arguments.build(vec!["-b".to_string(), value.to_string()]).unwrap_err();
+            assert_eq!(ParseErrorKind::InvalidValue, ParseError::from_error(&error).unwrap().kind(), "{}", value);
+        }
+        assert_eq!(1, buffer_parameter.get_value());
+        let error = arguments.build(vec!["-b".to_string(), "x".to_string()]).unwrap_err();
+        assert_eq!(ParseErrorKind::NotANumber, ParseError::from_error(&error).unwrap().kind());
+        assert!(PowerOfTwoParameter::try_new(0).is_err());
+        assert_eq!(8, PowerOfTwoParameter::try_new(8).unwrap().get_value());
+    }
+
+    #[test]
+    fn test_validators() {
+        let threads_parameter = IntParameter::new(4, validators::positive);
+        let port_parameter = IntParameter::new(6379, validators::in_range(1, 65535));
+        let buffer_parameter = SizeParameter::new(4096, validators::power_of_two);
+        let switches = [
             Switch::new("threads", Some('t'), None, &threads_parameter),
+            Switch::new("port", Some('p'), None, &port_parameter),
+            Switch::new("buffer", Some('b'), None, &buffer_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let args = ["-t", "8", "-p", "65535", "-b", "64K"].map(|a| a.to_string()).to_vec();
+        assert!(arguments.build(args).is_ok());
+        assert_eq!(8, threads_parameter.get_value());
+        assert_eq!(65535, port_parameter.get_value());
+        assert_eq!(65536, buffer_parameter.get_value());
+        assert!(arguments.build(vec!["-t".to_string(), "0".to_string()]).is_err());
+        assert!(arguments.build(vec!["-p".to_string(), "65536".to_string()]).is_err());
+        assert!(arguments.build(vec!["-b".to_string(), "3K".to_string()]).is_err());
+        assert!(validators::non_negative(0) && !validators::non_negative(-1));
+        assert!(validators::even(-2) && !validators::even(3));
+    }
+
+    #[test]
+    fn test_path_list_parameter() {
+        let libpath_parameter = PathListParameter::new(vec![]);
+        let switches = [Switch::new("libpath", None, Some("libpath"), &libpath_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let paths = vec![PathBuf::from("/a"), PathBuf::from("/b"), PathBuf::from("/c")];
+        let value = std::env::join_paths(&paths).unwrap().into_string().unwrap();
+        assert!(arguments.build(vec!["--libpath".to_string(), value.clone()]).is_ok());
+        assert_eq!(paths, libpath_parameter.get_values());
+        assert_eq!(value, libpath_parameter.value_string());
+    }
+
+    #[test]
+    fn test_required_missing() {
+        let host_parameter = StringParameter::new("");
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let user_parameter = StringParameter::new("");
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("host", Some('h'), None, &host_parameter).required(),
+            Switch::new("port", Some('p'), None, &port_parameter).required(),
+            Switch::new("user", Some('u'), None, &user_parameter).required(),
             Switch::new("verbose", Some('v'), None, &verbose_parameter),
-            Switch::new("test", None, Some("ss"), &string_parameter),
-            Switch::new("test_enum", Some('e'), None, &enum_parameter),
         ];
-        let mut arguments = Arguments::new("cache", &switches,
-                                           Some(vec!["arg1".to_string(), "arg2".to_string()]));
-        let result = arguments.build(vec![
-            "-p".to_string(), "3333".to_string(),
-            "-m".to_string(), "1M".to_string(),
-            "-t".to_string(), "12".to_string(),
-            "-v".to_string(),
-            "--ss".to_string(), "test".to_string(),
-            "-e".to_string(), "value".to_string(),
-            "arg1".to_string(), "arg2".to_string()]);
-        assert!(result.is_ok(), "{}", result.err().map(|e|e.to_string()).unwrap_or("".to_string()));
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let error = arguments.build(vec!["-p".to_string(), "3333".to_string()]).unwrap_err();
+        assert_eq!(ParseErrorKind::RequiredSwitchMissing, ParseError::from_error(&error).unwrap().kind());
+        assert_eq!("switch host is required", error.to_string());
+        assert_eq!(vec!["host".to_string(), "user".to_string()], arguments.required_missing());
+        let args = ["-h", "localhost", "-p", "3333", "-u", "admin"].map(|a| a.to_string()).to_vec();
+        assert!(arguments.build(args).is_ok());
+        assert!(arguments.required_missing().is_empty());
+    }
+
+    #[test]
+    fn test_parameter_from_env() {
+        std::env::set_var("ARGUMENTS_PARSER_TEST_PORT", "8080");
+        std::env::set_var("ARGUMENTS_PARSER_TEST_BAD_PORT", "-1");
+        std::env::set_var("ARGUMENTS_PARSER_TEST_MEMORY", "2M");
+        std::env::remove_var("ARGUMENTS_PARSER_TEST_NAME");
+        assert_eq!(8080, IntParameter::from_env("ARGUMENTS_PARSER_TEST_PORT", 6379, validators::positive).get_value());
+        let bad_port_parameter = IntParameter::from_env("ARGUMENTS_PARSER_TEST_BAD_PORT", 6379, validators::positive);
+        assert_eq!(6379, bad_port_parameter.get_value());
+        assert_eq!(2 * 1024 * 1024, SizeParameter::from_env("ARGUMENTS_PARSER_TEST_MEMORY", 1024, |v|v>0).get_value());
+        assert_eq!("cache", StringParameter::from_env("ARGUMENTS_PARSER_TEST_NAME", "cache").get_value());
+        let port_parameter = IntParameter::from_env("ARGUMENTS_PARSER_TEST_PORT", 6379, validators::positive);
+        let switches = [Switch::new("port", Some('p'), None, &port_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-p".to_string(), "3333".to_string()]).is_ok());
         assert_eq!(3333, port_parameter.get_value());
-        assert_eq!(1024 * 1024, max_memory_parameter.get_value());
-        assert_eq!(12, threads_parameter.get_value());
-        assert_eq!(true, verbose_parameter.get_value());
-        assert_eq!("test", string_parameter.get_value());
-        assert_eq!("value", enum_parameter.get_value());
-        assert_eq!(vec!["arg1".to_string(), "arg2".to_string()], arguments.get_other_arguments().clone());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_email_parameter() {
+        let notify_parameter = EmailParameter::new("");
+        let switches = [Switch::new("notify", None, Some("notify"), &notify_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--notify".to_string(), "admin@example.com".to_string()]).is_ok());
+        assert_eq!("admin@example.com", notify_parameter.get_value());
+        for value in ["not-an-email", "@example.com", "admin@", "admin@example", "a@b@example.com", "admin@example.",
+                      "ad min@example.com"] {
+            assert!(arguments.build(vec!["--notify".to_string(), value.to_string()]).is_err(), "{}", value);
+        }
+        assert_eq!("admin@example.com", notify_parameter.get_value());
+    }
+
+    #[test]
+    fn test_url_parameter() {
+        let endpoint_parameter = UrlParameter::new(vec!["http".to_string(), "https".to_string()], "");
+        let any_parameter = UrlParameter::new(vec![], "");
+        let switches = [
+            Switch::new("endpoint", None, Some("endpoint"), &endpoint_parameter),
+            Switch::new("any", None, Some("any"), &any_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--endpoint".to_string(), "HTTPS://host/path?x=1".to_string()]).is_ok());
+        assert_eq!("HTTPS://host/path?x=1", endpoint_parameter.get_value());
+        for value in ["ftp://host/file", "https://", "https:///path", "host/path", "1http://host"] {
+            assert!(arguments.build(vec!["--endpoint".to_string(), value.to_string()]).is_err(), "{}", value);
+        }
+        assert!(arguments.build(vec!["--any".to_string(), "ftp://host/file".to_string()]).is_ok());
+        assert_eq!("ftp://host/file", any_parameter.get_value());
+    }
+
+    #[test]
+    fn test_optional_parameter() {
+        let limit_parameter: OptionalParameter<u32> = OptionalParameter::new();
+        let name_parameter: OptionalParameter<String> = OptionalParameter::new();
+        let switches = [
+            Switch::new("limit", Some('l'), None, &limit_parameter),
+            Switch::new("name", Some('n'), None, &name_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert_eq!(None, limit_parameter.try_get_value());
+        assert!(arguments.build(vec!["-l".to_string(), "10".to_string()]).is_ok());
+        assert_eq!(Some(10), limit_parameter.try_get_value());
+        assert_eq!(None, name_parameter.try_get_value());
+        assert_eq!("", name_parameter.value_string());
+        assert!(arguments.build(vec!["-l".to_string(), "-1".to_string()]).is_err());
+        assert_eq!(Some(10), limit_parameter.try_get_value());
+    }
+
+    #[test]
+    fn test_require_one_of() {
+        let input_parameter = StringParameter::new("");
+        let stdin_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("input", Some('i'), Some("input-file"), &input_parameter),
+            Switch::new("stdin", None, Some("stdin"), &stdin_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        arguments.require_one_of(&["input-file", "stdin"]);
+        let error = arguments.build(vec![]).unwrap_err();
+        assert_eq!("exactly one of input-file, stdin is required", error.to_string());
+        assert!(arguments.build(vec!["-i".to_string(), "data.txt".to_string()]).is_ok());
+        assert!(arguments.build(vec!["--stdin".to_string()]).is_ok());
+        let args = ["--stdin", "--input-file", "data.txt"].map(|a| a.to_string()).to_vec();
+        assert!(arguments.build(args).is_err());
+    }
+
+    #[test]
+    fn test_switch_transform() {
+        let region_parameter = StringParameter::new("");
+        let level_parameter = EnumParameter::new(vec!["debug".to_string(), "info".to_string()], "info");
+        let switches = [
+            Switch::new("region", Some('r'), None, &region_parameter).transform(|v| v.to_uppercase()),
+            Switch::new("level", Some('l'), None, &level_parameter).transform(|v| v.to_lowercase()),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let args = ["-r", "eu-west", "-l", "DEBUG"].map(|a| a.to_string()).to_vec();
+        assert!(arguments.build(args).is_ok());
+        assert_eq!("EU-WEST", region_parameter.get_value());
+        assert_eq!("debug", level_parameter.get_value());
+    }
+
+    #[test]
+    fn test_usage_synopsis() {
+        let verbose_parameter = BoolParameter::new();
+        let quiet_parameter = BoolParameter::new();
+        let debug_parameter = BoolParameter::new();
+        let port_parameter = IntParameter::new(6379, |v|v>0);
+        let name_parameter = StringParameter::new("");
+        let dry_run_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+            Switch::new("port", Some('p'), Some("port"), &port_parameter),
+            Switch::new("quiet", Some('q'), None, &quiet_parameter),
+            Switch::new("name", None, Some("name"), &name_parameter).required(),
+            Switch::new("dry_run", None, Some("dry-run"), &dry_run_parameter),
+            Switch::new("debug", Some('d'), None, &debug_parameter),
+        ];
+        let arguments = Arguments::new("cache", &switches, Some(vec!["arg1".to_string(), "arg2".to_string()]));
+        let usage = arguments.usage_string();
+        assert_eq!("Usage: cache [-vqd] [-p PORT] --name NAME [--dry-run] arg1 arg2", usage.lines().next().unwrap());
+    }
+
+    #[test]
+    fn test_apply_env() {
+        std::env::set_var("ARGUMENTS_PARSER_TEST_ENV_HOST", "example.com");
+        std::env::set_var("ARGUMENTS_PARSER_TEST_ENV_PORT", "8080");
+        std::env::set_var("ARGUMENTS_PARSER_TEST_ENV_VERBOSE", "1");
+        let host_parameter = StringParameter::new("localhost");
+        let port_parameter = IntParameter::new(6379, validators::positive);
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("host", Some('h'), None, &host_parameter).env("ARGUMENTS_PARSER_TEST_ENV_HOST"),
+            Switch::new("port", Some('p'), None, &port_parameter).env("ARGUMENTS_PARSER_TEST_ENV_PORT"),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter).env("ARGUMENTS_PARSER_TEST_ENV_VERBOSE"),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-p".to_string(), "3333".to_string()]).is_ok());
+        assert!(arguments.apply_env().is_ok());
+        assert_eq!("example.com", host_parameter.get_value());
+        assert_eq!(3333, port_parameter.get_value());
+        assert!(verbose_parameter.get_value());
+        assert_eq!(ValueSource::Env, arguments.value_source("host"));
+        assert_eq!(ValueSource::Cli, arguments.value_source("port"));
+        assert_eq!(ValueSource::Env, arguments.value_source("verbose"));
+        assert!(arguments.build(vec![]).is_ok());
+        assert!(arguments.apply_env().is_ok());
+        assert_eq!(8080, port_parameter.get_value());
+        assert_eq!(ValueSource::Env, arguments.value_source("port"));
+        std::env::set_var("ARGUMENTS_PARSER_TEST_ENV_PORT", "-1");
+        assert!(arguments.build(vec![]).is_ok());
+        assert_eq!(ParseErrorKind::InvalidValue, arguments.apply_env().unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_build_full() {
+        let port_parameter = IntParameter::new(6379, validators::positive);
+        let switches = [Switch::new("port", Some('p'), None, &port_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let args = ["/usr/bin/cache", "-p", "3333", "arg1"].map(|a| a.to_string()).to_vec();
+        assert!(arguments.build_full(args).is_ok());
+        assert_eq!(3333, port_parameter.get_value());
+        assert_eq!(&vec!["arg1".to_string()], arguments.get_other_arguments());
+        assert!(arguments.build_full(vec![]).is_ok());
+        assert!(arguments.get_other_arguments().is_empty());
+    }
+
+    #[test]
+    fn test_stdin_or_value_string_parameter() {
+        let data_parameter = StdinOrValueStringParameter::with_reader("", || Ok("from stdin\n".to_string()));
+        let failing_parameter = StdinOrValueStringParameter::with_reader("",
+            || Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "no input")));
+        let switches = [
+            Switch::new("data", None, Some("data"), &data_parameter),
+            Switch::new("body", None, Some("body"), &failing_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--data".to_string(), "-".to_string()]).is_ok());
+        assert_eq!("from stdin\n", data_parameter.get_value());
+        assert!(arguments.build(vec!["--data".to_string(), "literal".to_string()]).is_ok());
+        assert_eq!("literal", data_parameter.get_value());
+        assert!(arguments.build(vec!["--body".to_string(), "-".to_string()]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_parse_error_to_json() {
+        let kinds = [
+            ParseErrorKind::InvalidValue, ParseErrorKind::NotANumber, ParseErrorKind::ValueOutOfRange,
+            ParseErrorKind::ValueExpected, ParseErrorKind::UnexpectedValue, ParseErrorKind::UnknownSwitch,
+            ParseErrorKind::InvalidSwitch, ParseErrorKind::IncorrectArgumentCount, ParseErrorKind::CommandExpected,
+            ParseErrorKind::UnknownCommand, ParseErrorKind::UnterminatedQuote, ParseErrorKind::NoGlobMatch,
+            ParseErrorKind::RequiredSwitchMissing, ParseErrorKind::ValidationFailed,
+        ];
+        for kind in kinds {
+            let error = ParseError::new(kind, None, "error");
+            let json: serde_json::Value = serde_json::from_str(&error.to_json()).unwrap();
+            assert_eq!(kind.name(), json["kind"]);
+            assert!(json["switch"].is_null());
+        }
+        let switches = [];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let error = arguments.build(vec!["-z".to_string()]).unwrap_err();
+        assert_eq!(r#"{"kind":"unknown_switch","message":"unknown switch","switch":"-z"}"#,
+                   ParseError::from_error(&error).unwrap().to_json());
+    }
+
+    #[test]
+    fn test_captures_rest() {
+        let exec_parameter = BoolParameter::new();
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("exec", Some('e'), Some("exec"), &exec_parameter).captures_rest(),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(["--exec", "prog", "-a", "-b"].map(|a| a.to_string()).to_vec()).is_ok());
+        assert_eq!(["prog".to_string(), "-a".to_string(), "-b".to_string()], arguments.captured("exec"));
+        assert!(exec_parameter.get_value());
+        assert!(!verbose_parameter.get_value());
+        assert!(arguments.build(vec!["-v".to_string()]).is_ok());
+        assert!(arguments.captured("exec").is_empty());
+        assert!(arguments.build(["-e", "prog", "-a"].map(|a| a.to_string()).to_vec()).is_ok());
+        assert_eq!(["prog".to_string(), "-a".to_string()], arguments.captured("exec"));
+        assert!(arguments.build(["-veprog", "-a"].map(|a| a.to_string()).to_vec()).is_ok());
+        assert_eq!(["prog".to_string(), "-a".to_string()], arguments.captured("exec"));
+        assert!(verbose_parameter.get_value());
+    }
+
+    #[test]
+    fn test_multi_enum_parameter() {
+        let allowed = ["a", "b", "c"].map(|v| v.to_string()).to_vec();
+        let features_parameter = MultiEnumParameter::new(allowed, vec![]);
+        let switches = [Switch::new("features", None, Some("features"), &features_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--features".to_string(), "a,c".to_string()]).is_ok());
+        assert_eq!(vec!["a".to_string(), "c".to_string()], features_parameter.get_value());
+        let result = arguments.build(vec!["--features".to_string(), "b,x,c".to_string()]);
+        assert_eq!("x is not one of the allowed values", result.unwrap_err().to_string());
+        assert_eq!(vec!["a".to_string(), "c".to_string()], features_parameter.get_value());
+        assert!(arguments.build(vec![]).is_ok());
+        let args = ["--features", "zz", "--features", "a"].map(|a| a.to_string()).to_vec();
+        assert!(arguments.build(args).is_err());
+        assert_eq!(" a|b|c[,...]", features_parameter.value_type());
+    }
+
+    #[test]
+    fn test_positional_count() {
+        let verbose_parameter = BoolParameter::new();
+        let switches = [Switch::new("verbose", Some('v'), None, &verbose_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-v".to_string()]).is_ok());
+        assert_eq!(0, arguments.positional_count());
+        assert!(!arguments.has_positionals());
+        assert!(arguments.build(["a", "-v", "b", "c"].map(|a| a.to_string()).to_vec()).is_ok());
+        assert_eq!(3, arguments.positional_count());
+        assert!(arguments.has_positionals());
+    }
+
+    #[test]
+    fn test_int_parameter_default_validation() {
+        assert!(IntParameter::try_new(-1, validators::positive).is_err());
+        assert_eq!(8080, IntParameter::try_new(8080, validators::positive).unwrap().get_value());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_power_of_two_parameter_invalid_default() {
+        PowerOfTwoParameter::new(1000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_int_parameter_invalid_default() {
+        IntParameter::new(0, validators::positive);
+    }
+
+    #[test]
+    fn test_switch_lookup() {
+        let port_parameter = IntParameter::new(6379, validators::positive);
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("port", Some('p'), Some("port"), &port_parameter),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter),
+        ];
+        let arguments = Arguments::new("cache", &switches, None);
+        for key in ["-p", "--port", "port"] {
+            let switch = arguments.switch(key).unwrap();
+            assert_eq!("port", switch.get_name());
+            assert_eq!(Some('p'), switch.get_short());
+            assert_eq!(Some("port"), switch.get_long());
+        }
+        assert_eq!(None, arguments.switch("-v").unwrap().get_long());
+        assert!(arguments.switch("--verbose").is_none());
+        assert!(arguments.switch("-x").is_none());
+    }
+
+    #[test]
+    fn test_attached_value_with_equals() {
+        let filter_parameter = StringParameter::new("");
+        let switches = [Switch::new("filter", None, Some("filter"), &filter_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--filter=a=b".to_string()]).is_ok());
+        assert_eq!("a=b", filter_parameter.get_value());
+        assert!(arguments.build(vec!["--filter".to_string(), "c=d=e".to_string()]).is_ok());
+        assert_eq!("c=d=e", filter_parameter.get_value());
+    }
+
+    #[test]
+    fn test_key_value_parameter_from_env_prefix() {
+        std::env::set_var("ARGUMENTS_PARSER_PREFIX_HOST", "example.com");
+        std::env::set_var("ARGUMENTS_PARSER_PREFIX_PORT", "8080");
+        let define_parameter = LastWinsKeyValueParameter::from_env_prefix("ARGUMENTS_PARSER_PREFIX_", HashMap::new());
+        let switches = [Switch::new("define", Some('D'), None, &define_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-DPORT=3333".to_string()]).is_ok());
+        let values = define_parameter.get_values();
+        assert_eq!(2, values.len());
+        assert_eq!("example.com", values["HOST"]);
+        assert_eq!("3333", values["PORT"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_int_parameter_default_below_min() {
+        IntParameter::new(0, |_| true).with_min(1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_size_parameter_negative_default() {
+        SizeParameter::new(-1, |_| true);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_size_parameter_default_above_max() {
+        SizeParameter::new(4096, |_| true).with_max(1024);
+    }
+
+    #[test]
+    fn test_min_max_bounds() {
+        let port_parameter = IntParameter::new(6379, |v| v != 8080).with_min(1).with_max(65535);
+        let memory_parameter = SizeParameter::new(1024, |_| true).with_min(512).with_max(1024 * 1024);
+        let switches = [
+            Switch::new("port", Some('p'), None, &port_parameter),
+            Switch::new("memory", Some('m'), None, &memory_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-p".to_string(), "3333".to_string()]).is_ok());
+        assert_eq!(3333, port_parameter.get_value());
+        assert!(arguments.build(vec!["-p".to_string(), "0".to_string()]).is_err());
+        assert!(arguments.build(vec!["-p".to_string(), "65536".to_string()]).is_err());
+        assert!(arguments.build(vec!["-p".to_string(), "8080".to_string()]).is_err());
+        assert_eq!(3333, port_parameter.get_value());
+        assert!(arguments.build(vec!["-m".to_string(), "1K".to_string()]).is_ok());
+        assert!(arguments.build(vec!["-m".to_string(), "256".to_string()]).is_err());
+        assert!(arguments.build(vec!["-m".to_string(), "2M".to_string()]).is_err());
+        assert_eq!(1024, memory_parameter.get_value());
+    }
+
+    #[test]
+    fn test_quoted_csv_parameter() {
+        let names_parameter = QuotedCsvParameter::new(vec![]);
+        let switches = [Switch::new("names", None, Some("names"), &names_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--names".to_string(), r#""Doe, John",Alice"#.to_string()]).is_ok());
+        assert_eq!(vec!["Doe, John".to_string(), "Alice".to_string()], names_parameter.get_values());
+        assert_eq!(r#""Doe, John",Alice"#, names_parameter.value_string());
+        assert!(arguments.build(vec!["--names".to_string(), r#""say ""hi""",b"#.to_string()]).is_ok());
+        assert_eq!(vec![r#"say "hi""#.to_string(), "b".to_string()], names_parameter.get_values());
+        assert!(arguments.build(vec!["--names".to_string(), r#""Doe, John,Alice"#.to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_describe() {
+        let port_parameter = IntParameter::new(6379, validators::positive);
+        let verbose_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("port", Some('p'), Some("port"), &port_parameter).required().description("port to listen on"),
+            Switch::new("verbose", Some('v'), None, &verbose_parameter).category("Logging").hidden(),
+        ];
+        let arguments = Arguments::new("cache", &switches, Some(vec!["arg1".to_string(), "arg2".to_string()]));
+        let spec = arguments.describe();
+        assert_eq!("cache", spec.program_name);
+        assert_eq!(2, spec.positionals.len());
+        assert_eq!(2, spec.switches.len());
+        let port = &spec.switches[0];
+        assert_eq!((Some('p'), Some("port")), (port.short, port.long.as_deref()));
+        assert_eq!(("int", true, "6379"), (port.value_type.as_str(), port.required, port.default.as_str()));
+        assert_eq!(Some("port to listen on".to_string()), port.description);
+        assert_eq!((None, false), (port.category.as_deref(), port.hidden));
+        let verbose = &spec.switches[1];
+        assert_eq!("", verbose.value_type);
+        assert_eq!((Some("Logging"), true), (verbose.category.as_deref(), verbose.hidden));
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_value(&spec).unwrap();
+            assert_eq!(3, json.as_object().unwrap().len());
+            assert_eq!(9, json["switches"][0].as_object().unwrap().len());
+        }
+    }
+
+    #[test]
+    fn test_signed_size_parameter() {
+        let grow_parameter = SignedSizeParameter::new(0);
+        let shrink_parameter = SignedSizeParameter::new(0);
+        let switches = [
+            Switch::new("grow", None, Some("grow"), &grow_parameter),
+            Switch::new("shrink", None, Some("shrink"), &shrink_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(["--grow", "+10G", "--shrink", "-2G"].map(|a| a.to_string()).to_vec()).is_ok());
+        assert_eq!(10 * 1024 * 1024 * 1024, grow_parameter.get_value());
+        assert!(grow_parameter.is_grow());
+        assert_eq!("+10737418240", grow_parameter.value_string());
+        assert_eq!(-2 * 1024 * 1024 * 1024, shrink_parameter.get_value());
+        assert!(!shrink_parameter.is_grow());
+        assert!(arguments.build(vec!["--grow".to_string(), "10G".to_string()]).is_err());
+        assert!(arguments.build(vec!["--grow=+".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_usage_header() {
+        let port_parameter = IntParameter::new(6379, validators::positive);
+        let switches = [Switch::new("port", Some('p'), None, &port_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, Some(vec!["arg1".to_string()]));
+        arguments.set_usage_header("USAGE:\n  {program} {switches} {positionals}");
+        assert_eq!("USAGE:\n  cache [-p PORT] arg1\n -p int - port\n", arguments.usage_string());
+        arguments.set_usage_header("{program} - key-value cache");
+        assert_eq!("cache - key-value cache\n -p int - port\n", arguments.usage_string());
+    }
+
+    #[test]
+    fn test_short_switch_cluster_order() {
+        let a_parameter = BoolParameter::new();
+        let b_parameter = BoolParameter::new();
+        let c_parameter = BoolParameter::new();
+        let port_parameter = StringParameter::new("");
+        let switches = [
+            Switch::new("a", Some('a'), None, &a_parameter),
+            Switch::new("b", Some('b'), None, &b_parameter),
+            Switch::new("c", Some('c'), None, &c_parameter),
+            Switch::new("port", Some('p'), None, &port_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-abcp3333".to_string()]).is_ok());
+        assert!(a_parameter.get_value() && b_parameter.get_value() && c_parameter.get_value());
+        assert_eq!("3333", port_parameter.get_value());
+        let a_parameter = BoolParameter::new();
+        let b_parameter = BoolParameter::new();
+        let c_parameter = BoolParameter::new();
+        let switches = [
+            Switch::new("a", Some('a'), None, &a_parameter),
+            Switch::new("b", Some('b'), None, &b_parameter),
+            Switch::new("c", Some('c'), None, &c_parameter),
+            Switch::new("port", Some('p'), None, &port_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-pabc3333".to_string()]).is_ok());
+        assert_eq!("abc3333", port_parameter.get_value());
+        assert!(!a_parameter.get_value() && !b_parameter.get_value() && !c_parameter.get_value());
+        assert_eq!(1, arguments.parsed_switch_count());
+    }
+
+    #[test]
+    fn test_bounded_string_parameter() {
+        let comment_parameter = BoundedStringParameter::new(4, "");
+        let name_parameter = BoundedStringParameter::new(4, "").count_chars();
+        let switches = [
+            Switch::new("comment", None, Some("comment"), &comment_parameter),
+            Switch::new("name", None, Some("name"), &name_parameter),
+        ];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["--comment".to_string(), "abcd".to_string()]).is_ok());
+        assert_eq!("abcd", comment_parameter.get_value());
+        assert!(arguments.build(vec!["--comment".to_string(), "abcde".to_string()]).is_err());
+        // "äöü" is 3 characters, but 6 bytes
+        assert!(arguments.build(vec!["--comment".to_string(), "äöü".to_string()]).is_err());
+        assert_eq!("abcd", comment_parameter.get_value());
+        assert!(arguments.build(vec!["--name".to_string(), "äöü".to_string()]).is_ok());
+        assert_eq!("äöü", name_parameter.get_value());
+    }
+
+    #[test]
+    fn test_write_error() {
+        let port_parameter = IntParameter::new(6379, validators::positive);
+        let switches = [Switch::new("port", Some('p'), None, &port_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let error = arguments.build(vec!["-p".to_string(), "abc".to_string()]).unwrap_err();
+        let mut buffer = Vec::new();
+        assert!(arguments.write_error(&mut buffer, &error).is_ok());
+        assert_eq!("port value is not a number\nUsage: cache [-p PORT]\n -p int - port\n\n",
+                   String::from_utf8(buffer).unwrap());
+    }
+
+    #[test]
+    fn test_mapped_enum_parameter() {
+        let speeds = vec![("slow".to_string(), 1000), ("medium".to_string(), 2000), ("fast".to_string(), 3000)];
+        let speed_parameter = MappedEnumParameter::new(speeds, 0);
+        let switches = [Switch::new("speed", None, Some("speed"), &speed_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert_eq!(" slow|medium|fast", speed_parameter.value_type());
+        assert_eq!("", speed_parameter.value_string());
+        assert!(arguments.build(vec!["--speed".to_string(), "fast".to_string()]).is_ok());
+        assert_eq!(3000, speed_parameter.get_value());
+        assert_eq!("fast", speed_parameter.get_key());
+        assert_eq!("fast", speed_parameter.value_string());
+        assert!(arguments.build(vec!["--speed".to_string(), "warp".to_string()]).is_err());
+        assert_eq!(3000, speed_parameter.get_value());
+        let speeds = vec![("slow".to_string(), 1000), ("medium".to_string(), 2000)];
+        let speed_parameter = MappedEnumParameter::new(speeds, 2000);
+        let switches = [Switch::new("speed", None, Some("speed"), &speed_parameter)];
+        let arguments = Arguments::new("cache", &switches, None);
+        assert_eq!("medium", speed_parameter.value_string());
+        assert_eq!("", speed_parameter.get_key());
+        assert!(arguments.usage_for("speed").unwrap().contains("  default: medium\n"));
+    }
+
+    #[test]
+    fn test_defer_positional_validation() {
+        let mut arguments = Arguments::new("cache", &[], Some(vec!["file".to_string()]));
+        assert!(arguments.build(vec![]).is_err());
+        arguments.set_defer_positional_validation(true);
+        assert!(arguments.build(vec![]).is_ok());
+        assert_eq!(0, arguments.positional_count());
+        assert!(arguments.build(vec!["a".to_string(), "b".to_string()]).is_ok());
+        assert_eq!(2, arguments.positional_count());
+    }
+
+    #[test]
+    fn test_decimal_scaled_parameter() {
+        let count_parameter = DecimalScaledParameter::new(0);
+        let switches = [Switch::new("count", Some('c'), None, &count_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-c".to_string(), "1k".to_string()]).is_ok());
+        assert_eq!(1000, count_parameter.get_value());
+        assert!(arguments.build(vec!["-c".to_string(), "2M".to_string()]).is_ok());
+        assert_eq!(2_000_000, count_parameter.get_value());
+        assert!(arguments.build(vec!["-c".to_string(), "42".to_string()]).is_ok());
+        assert_eq!(42, count_parameter.get_value());
+        assert!(arguments.build(vec!["-c".to_string(), "20000000T".to_string()]).is_err());
+        assert!(arguments.build(vec!["-c".to_string(), "k".to_string()]).is_err());
+        assert_eq!(42, count_parameter.get_value());
+    }
+
+    #[test]
+    fn test_switch_without_short_and_long() {
+        let port_parameter = IntParameter::new(6379, validators::positive);
+        let switches = [Switch::new("port", None, None, &port_parameter)];
+        let error = Arguments::try_new("cache", &switches, None).err().unwrap();
+        assert_eq!("switch port has neither a short nor a long switch", error.to_string());
+        let mut arguments = Arguments::try_new("cache", &[], None).unwrap();
+        assert!(arguments.extend(&[Switch::for_handler("port", &port_parameter)]).is_err());
+        assert!(arguments.extend(&[Switch::for_handler("port", &port_parameter).long("port")]).is_ok());
+    }
+
+    #[test]
+    fn test_colon_key_value_parameter() {
+        let header_parameter = ColonKeyValueParameter::new(HashMap::new());
+        let switches = [Switch::new("header", Some('H'), Some("header"), &header_parameter)];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        let args = ["--header", "Content-Type:application/json", "-H", "Origin:http://localhost:8080"]
+            .map(|a| a.to_string()).to_vec();
+        assert!(arguments.build(args).is_ok());
+        let values = header_parameter.get_values();
+        assert_eq!("application/json", values["Content-Type"]);
+        assert_eq!("http://localhost:8080", values["Origin"]);
+        assert!(arguments.build(vec!["-H".to_string(), "Content-Type".to_string()]).is_err());
+        assert!(arguments.build(vec!["-H".to_string(), ":json".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_boxed_handlers() {
+        let mut switches = Vec::new();
+        for name in ["host", "user"] {
+            switches.push(Switch::new_boxed(name, None, Some(name), Box::new(StringParameter::new("default"))));
+        }
+        let port_parameter = IntParameter::new(6379, validators::positive);
+        switches.push(Switch::new_boxed("port", Some('p'), None, Box::new(port_parameter)));
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert_eq!(Some(6379), arguments.get_value::<isize>("port"));
+        let args = ["--host", "example.com", "-p", "3333"].map(|a| a.to_string()).to_vec();
+        assert!(arguments.build(args).is_ok());
+        assert_eq!(Some("example.com".to_string()), arguments.get_value::<String>("--host"));
+        assert_eq!(Some("default".to_string()), arguments.get_value::<String>("user"));
+        assert_eq!(Some(3333), arguments.get_value::<isize>("-p"));
+        assert_eq!(None, arguments.get_value::<isize>("host"));
+        assert_eq!(None, arguments.get_value::<String>("missing"));
+        assert!(arguments.handler::<StringParameter>("host").is_none());
+
+        let switches = [Switch::new_typed("temp", Some('t'), None, TemperatureParameter::new(20.0))];
+        let mut arguments = Arguments::new("cache", &switches, None);
+        assert!(arguments.build(vec!["-t".to_string(), "212F".to_string()]).is_ok());
+        assert_eq!(None, arguments.get_value::<f64>("temp"));
+        assert_eq!(Some(100.0), arguments.handler::<TemperatureParameter>("temp").map(|t| t.get_value()));
+        assert!(arguments.handler::<IntParameter>("temp").is_none());
+    }
+}
@@ -0,0 +1,22 @@
+//! Ready-made validators for IntParameter and SizeParameter, e.g. IntParameter::new(4, validators::positive).
+
+pub fn positive(v: isize) -> bool {
+    v > 0
+}
+
+pub fn non_negative(v: isize) -> bool {
+    v >= 0
+}
+
+pub fn even(v: isize) -> bool {
+    v % 2 == 0
+}
+
+pub fn power_of_two(v: isize) -> bool {
+    v > 0 && (v & (v - 1)) == 0
+}
+
+/// Accepts values from lo to hi inclusive, e.g. in_range(1, 65535) for a port.
+pub fn in_range(lo: isize, hi: isize) -> impl Fn(isize) -> bool {
+    move |v| (lo..=hi).contains(&v)
+}